@@ -1,19 +1,26 @@
 #![allow(non_snake_case)]
+use crate::controls::{Control, RawTerminal};
+use crate::device::{self, DeviceChoice, Direction};
+use crate::error::{Error, Result};
+use crate::flac::{self, FlacSettings};
+use crate::internal_encoding::opus_sample_rate;
+use crate::metadata::Metadata;
+use crate::resample::{self, ResampleQuality};
+use crate::spool::Spool;
+use crate::stream::{self, PlayState, RecordState, Taker, send_input_data, write_output_data};
+use crate::wav::{self, WavSettings};
 use chrono::prelude::*;
-use color_eyre::eyre::{Result, eyre};
-use cpal::{
-    Sample,
-    traits::{DeviceTrait, HostTrait, StreamTrait},
-};
-use dasp::{Signal, interpolate::linear::Linear, signal};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use rtrb::RingBuffer;
 use std::sync::{
-    Arc, Mutex,
-    mpsc::{Sender, channel},
+    Arc,
+    atomic::Ordering,
+    mpsc::{RecvTimeoutError, channel},
 };
 
 use symphonia::core::audio::SampleBuffer;
 use symphonia::core::codecs::DecoderOptions;
-use symphonia::core::errors::Error;
+use symphonia::core::errors::Error as SymphoniaError;
 use symphonia::core::formats::FormatOptions;
 use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::MetadataOptions;
@@ -21,14 +28,14 @@ use symphonia::core::probe::Hint;
 
 use std::fs::File;
 use std::path::Path;
+use std::time::Duration;
 
-use hound;
-
-///Raw Mono Audio Data
+///Raw Audio Data, the samples of every channel are interleaved
 #[derive(Clone)]
 pub struct AudioClip {
     pub samples: Vec<f32>,
     pub sample_rate: u32, // Most Common -> 48kHz : 44.1kHz
+    pub channels: u16,
 
     // decided to save the meta data `date-time` to be stored here
     pub id: Option<usize>,
@@ -36,12 +43,176 @@ pub struct AudioClip {
     pub date: DateTime<Utc>,
 }
 
-type ClipHandle = Arc<Mutex<Option<AudioClip>>>;
+/// A point in a clip marked while recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Marker {
+    // milliseconds from the start of the clip, paused stretches don't count
+    pub position_ms: u64,
+}
+
+/// When a recording stops by itself, so one left running can't fill up the memory
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordLimits {
+    /// Longest the clip can get, paused stretches don't count
+    pub max_duration: Option<Duration>,
+    /// Stop once it has been quiet for this long, the quiet part is cut off
+    pub stop_on_silence: Option<Duration>,
+    /// Anything quieter than this (in dBFS) counts as silence
+    pub silence_threshold_db: f32,
+    /// Most memory the samples can take, the recording stops and is saved at this point.
+    /// That counts the copy a clip at a rate opus can't store is resampled into for saving
+    pub max_bytes: usize,
+}
+
+impl Default for RecordLimits {
+    fn default() -> Self {
+        Self {
+            max_duration: None,
+            stop_on_silence: None,
+            silence_threshold_db: -50.0,
+            // a bit over 3 hours of mono at 48kHz
+            max_bytes: 2 << 30,
+        }
+    }
+}
+
+impl RecordLimits {
+    /// Checks the limits can ever let anything be recorded
+    pub fn validate(&self) -> Result<()> {
+        if self.max_duration == Some(Duration::ZERO) {
+            return Err(Error::InvalidRecordLimits(
+                "the longest duration has to be above 0".to_string(),
+            ));
+        }
+        if self.stop_on_silence == Some(Duration::ZERO) {
+            return Err(Error::InvalidRecordLimits(
+                "the silence to stop at has to be above 0".to_string(),
+            ));
+        }
+        if !self.silence_threshold_db.is_finite() {
+            return Err(Error::InvalidRecordLimits(format!(
+                "silence threshold {} isn't a level",
+                self.silence_threshold_db
+            )));
+        }
+        if self.max_bytes == 0 {
+            return Err(Error::InvalidRecordLimits(
+                "the memory limit has to be above 0".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Frames of `channels` channels at `sample_rate` that fit in `max_bytes`.
+    // A clip at a rate opus can't do is resampled before it is saved, and for a while
+    // both copies are around, so the resampled one has to fit as well
+    pub(crate) fn memory_frames(&self, channels: u16, sample_rate: u32) -> usize {
+        let frame_bytes = (std::mem::size_of::<f32>() * channels.max(1) as usize) as u128;
+        let sample_rate = sample_rate.max(1) as u128;
+        let stored_rate = match opus_sample_rate(sample_rate as u32) as u128 {
+            rate if rate == sample_rate => 0,
+            rate => rate,
+        };
+
+        (self.max_bytes as u128 * sample_rate / (frame_bytes * (sample_rate + stored_rate)))
+            .try_into()
+            .unwrap_or(usize::MAX)
+    }
+
+    // Frames in `max_duration` at `sample_rate`
+    pub(crate) fn duration_frames(&self, sample_rate: u32) -> usize {
+        self.max_duration
+            .map(|duration| (duration.as_secs_f64() * sample_rate as f64).ceil() as usize)
+            .unwrap_or(usize::MAX)
+    }
+}
+
+// How much audio the rings between the streams and the threads around them hold
+const RECORD_RING: Duration = Duration::from_secs(2);
+const PLAY_RING: Duration = Duration::from_millis(500);
+
+// How often the ring is topped up while playing
+const FEED_INTERVAL: Duration = Duration::from_millis(20);
+
+// Samples in a ring holding `duration` of `channels` channels, always whole frames
+fn ring_len(duration: Duration, sample_rate: u32, channels: u16) -> usize {
+    let frames = (duration.as_secs_f64() * sample_rate as f64).ceil() as usize;
+    frames.max(1) * channels.max(1) as usize
+}
+
+// How often the limits are checked while waiting for a key
+const LIMIT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// Why a recording ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stop {
+    Asked,
+    MaxDuration,
+    MemoryLimit,
+    Silence,
+}
+
+impl Stop {
+    fn message(self) -> &'static str {
+        match self {
+            Stop::Asked => "Got it! Stopping recording...",
+            Stop::MaxDuration => "Reached the longest the clip can be, stopping recording...",
+            Stop::MemoryLimit => "Reached the memory limit, stopping recording...",
+            Stop::Silence => "Silent for long enough, stopping recording...",
+        }
+    }
+}
+
+// `m:ss.s`, for the lines printed while recording
+fn format_position(ms: u64) -> String {
+    format!(
+        "{}:{:02}.{}",
+        ms / 60_000,
+        (ms / 1000) % 60,
+        (ms % 1000) / 100
+    )
+}
+
+/// How the channels of a clip are turned into one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Downmix {
+    /// The average of all channels
+    Average,
+    /// Only the first channel
+    Left,
+    /// Only the second channel
+    Right,
+    /// The channel with the most energy, for when only one of them has anything on it
+    MaxEnergy,
+    /// Only the channel with this index, starting at 0
+    Channel(usize),
+}
+
+impl std::str::FromStr for Downmix {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "average" => Ok(Downmix::Average),
+            "left" => Ok(Downmix::Left),
+            "right" => Ok(Downmix::Right),
+            "max-energy" => Ok(Downmix::MaxEnergy),
+            _ => s.parse().map(Downmix::Channel).map_err(|_| {
+                format!(
+                    "expected average, left, right, max-energy or a channel index, got `{}`",
+                    s
+                )
+            }),
+        }
+    }
+}
 
 impl AudioClip {
     #[allow(dead_code)]
     pub fn new(
         sample_rate: u32,
+        channels: u16,
         samples: Vec<f32>,
         id: Option<usize>,
         name: String,
@@ -50,199 +221,527 @@ impl AudioClip {
         Self {
             samples,
             sample_rate,
+            channels,
             id,
             name,
             date,
         }
     }
 
-    pub fn record(_name: String) -> Result<AudioClip> {
-        let host = cpal::default_host();
-        let device = host
-            .default_input_device()
-            .ok_or_else(|| eyre!("No input device found"))?;
+    /// Number of samples in every channel
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    /// The clip with all of its channels averaged into one
+    pub fn to_mono(&self) -> AudioClip {
+        let channels = self.channels.max(1) as usize;
 
-        println!("Input Device: {}", device.name()?);
+        AudioClip {
+            samples: self
+                .samples
+                .chunks(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+                .collect(),
+            channels: 1,
+            ..self.clone()
+        }
+    }
 
-        let config = device.default_input_config()?;
+    // Records until it is stopped, in stereo if `stereo` is set and the device has two channels
+    pub fn record(_name: String, stereo: bool) -> Result<AudioClip> {
+        Ok(Self::record_on(
+            _name,
+            stereo,
+            &DeviceChoice::default(),
+            &RecordLimits::default(),
+            None,
+        )?
+        .0)
+    }
 
+    /// Records like `record`, from the device and at the rate `choice` asks for.
+    ///
+    /// In a terminal space pauses and resumes, `m` marks the current position and `q` or Enter stop.
+    /// Nothing is kept while paused. Without a terminal it records until ctrl-c is pressed.
+    /// Either way it stops by itself once one of the `limits` is reached.
+    /// The markers are returned along with the clip, in the order they were made.
+    /// With a `spool` the recording is also written to disk as it goes, see `Spool`
+    pub fn record_on(
+        _name: String,
+        stereo: bool,
+        choice: &DeviceChoice,
+        limits: &RecordLimits,
+        spool: Option<&Spool>,
+    ) -> Result<(AudioClip, Vec<Marker>)> {
+        limits.validate()?;
+
+        let device = device::device(Direction::Input, choice)?;
+        let config = device::config(&device, Direction::Input, choice.rate)?;
+
+        println!(
+            "Input Device: {} ({} Hz)",
+            device.name()?,
+            config.sample_rate().0
+        );
+
+        let channels = config.channels();
+        // the channels past the first two are dropped, opus can only do stereo
+        let kept_channels = if stereo { channels.min(2) } else { 1 };
+        if stereo && channels < 2 {
+            eprintln!("The input device only has one channel, recording in mono");
+        }
+
+        let sample_rate = config.sample_rate().0;
         let clip = AudioClip {
             id: None,
             date: Utc::now(),
             samples: Vec::new(),
             name: _name,
-            sample_rate: config.sample_rate().0,
+            sample_rate,
+            channels: kept_channels,
         };
 
+        let spool = spool
+            .map(|spool| spool.writer(&clip.name, clip.date, kept_channels, sample_rate))
+            .transpose()?;
+
         println!("Begin Recording...");
 
-        let clip = Arc::new(Mutex::new(Some(clip)));
-        let clip_2 = clip.clone();
+        let mut taker = Taker::new(clip, limits, spool);
+        let silent_frames_to_stop = limits
+            .stop_on_silence
+            .map(|silence| (silence.as_secs_f64() * sample_rate as f64).ceil() as usize);
+
+        let state = Arc::new(RecordState::default());
+        let state_2 = state.clone();
+        let (mut samples_tx, mut samples_rx) =
+            RingBuffer::new(ring_len(RECORD_RING, sample_rate, kept_channels));
 
         let err_fn = move |err| {
             eprintln!("an error occurred on stream: {}", err);
         };
 
-        let channels = config.channels();
-        /// We are just going to focus on mono channel data.
-        /// We can go with panoramic audio if we want to, but for a Voice Journal is it Necessary?
-
-        fn write_input_data<T>(input: &[T], channels: u16, writer: &ClipHandle)
-        where
-            T: Sample,
-            f32: cpal::FromSample<T>,
-        {
-            if let Ok(mut guard) = writer.try_lock() {
-                if let Some(clip) = guard.as_mut() {
-                    for frame in input.chunks(channels.into()) {
-                        clip.samples.push(frame[0].to_sample::<f32>());
-                    }
-                }
-            }
-        }
-
         let stream = match config.sample_format() {
             cpal::SampleFormat::F32 => device.build_input_stream(
                 &config.into(),
-                move |data, _: &_| write_input_data::<f32>(data, channels, &clip_2),
+                move |data, _: &_| {
+                    send_input_data::<f32>(data, channels, kept_channels, &state_2, &mut samples_tx)
+                },
                 err_fn,
                 None,
             )?,
 
             cpal::SampleFormat::I16 => device.build_input_stream(
                 &config.into(),
-                move |data, _: &_| write_input_data::<i16>(data, channels, &clip_2),
+                move |data, _: &_| {
+                    send_input_data::<i16>(data, channels, kept_channels, &state_2, &mut samples_tx)
+                },
                 err_fn,
                 None,
             )?,
 
             cpal::SampleFormat::U16 => device.build_input_stream(
                 &config.into(),
-                move |data, _: &_| write_input_data::<u16>(data, channels, &clip_2),
+                move |data, _: &_| {
+                    send_input_data::<u16>(data, channels, kept_channels, &state_2, &mut samples_tx)
+                },
                 err_fn,
                 None,
             )?,
-            _ => return Err(eyre!("Unsupported Sample Format")), // maybe deal with this somewhen
+            _ => return Err(Error::UnsupportedSampleFormat), // maybe deal with this somewhen
         };
 
         stream.play()?;
 
-        let (tx, rx) = channel();
-        ctrlc::set_handler(move || {
-            tx.send(())
-                .expect("Could not send signal over the chanel...")
-        })?;
-        println!("Press Ctrl-C to stop recording...");
-        rx.recv()?;
-        println!("\nGot it! Stopping recording...");
+        // why the recording has to stop by itself, if it does
+        let limit_reached = |taker: &Taker| {
+            if taker.clip().frames() >= taker.max_frames() {
+                return Some(
+                    if taker.max_frames() < limits.memory_frames(kept_channels, sample_rate) {
+                        Stop::MaxDuration
+                    } else {
+                        Stop::MemoryLimit
+                    },
+                );
+            }
+
+            match silent_frames_to_stop {
+                Some(limit) if taker.silent_frames() >= limit => Some(Stop::Silence),
+                _ => None,
+            }
+        };
+
+        let mut markers = Vec::new();
+
+        let stop = if let Some(terminal) = RawTerminal::enable() {
+            terminal.status("Space pauses and resumes, m adds a marker, q or Enter stops");
+
+            let stop = loop {
+                taker.take(&mut samples_rx);
+                if let Some(stop) = limit_reached(&taker) {
+                    break stop;
+                }
+
+                match terminal.poll(LIMIT_CHECK_INTERVAL)? {
+                    Some(Control::PauseResume) => {
+                        taker.take(&mut samples_rx);
+                        let paused = !state.paused.fetch_xor(true, Ordering::Relaxed);
+                        let at = format_position(taker.position_ms());
+                        if paused {
+                            terminal.status(&format!("Paused at {}, space resumes", at));
+                        } else {
+                            terminal.status(&format!("Recording again from {}", at));
+                        }
+                    }
+                    Some(Control::Marker) => {
+                        taker.take(&mut samples_rx);
+                        let marker = Marker {
+                            position_ms: taker.position_ms(),
+                        };
+                        markers.push(marker);
+                        taker.spool(|spool| spool.marker(marker));
+                        terminal.status(&format!(
+                            "Marker {} at {}",
+                            markers.len(),
+                            format_position(marker.position_ms)
+                        ));
+                    }
+                    Some(Control::Stop) => break Stop::Asked,
+                    None => (),
+                }
+            };
+
+            terminal.status(stop.message());
+            stop
+        } else {
+            let (tx, rx) = channel();
+            ctrlc::set_handler(move || {
+                tx.send(())
+                    .expect("Could not send signal over the chanel...")
+            })?;
+            println!("Press Ctrl-C to stop recording...");
+
+            let stop = loop {
+                taker.take(&mut samples_rx);
+                if let Some(stop) = limit_reached(&taker) {
+                    break stop;
+                }
+
+                match rx.recv_timeout(LIMIT_CHECK_INTERVAL) {
+                    Err(RecvTimeoutError::Timeout) => (),
+                    _ => break Stop::Asked,
+                }
+            };
+
+            println!("\n{}", stop.message());
+            stop
+        };
 
         drop(stream);
-        let clip = clip.lock().unwrap().take().unwrap();
+        // whatever came in since the last look
+        taker.take(&mut samples_rx);
+        let silent_frames = taker.silent_frames();
+        let mut clip = taker.into_clip();
+
+        if stop == Stop::Silence {
+            stream::cut_silence(&mut clip, silent_frames, &mut markers);
+        }
 
-        eprintln!("Recorded {} samples", clip.samples.len());
-        Ok(clip)
+        eprintln!(
+            "Recorded {} samples, {} overrun(s)",
+            clip.frames(),
+            state.overruns.load(Ordering::Relaxed)
+        );
+        Ok((clip, markers))
     }
 
-    pub fn play(&self) -> Result<()> {
-        let host = cpal::default_host();
-        let device = host
-            .default_output_device()
-            .ok_or_else(|| eyre!("No output device found"))?;
+    pub fn play(&self, quality: ResampleQuality) -> Result<()> {
+        self.play_on(quality, &DeviceChoice::default())
+    }
 
-        println!("Output Device: {}", device.name()?);
+    /// Plays like `play`, on the device and at the rate `choice` asks for
+    pub fn play_on(&self, quality: ResampleQuality, choice: &DeviceChoice) -> Result<()> {
+        let device = device::device(Direction::Output, choice)?;
+        let config = device::config(&device, Direction::Output, choice.rate)?;
 
-        let config = device.default_output_config()?;
+        println!(
+            "Output Device: {} ({} Hz)",
+            device.name()?,
+            config.sample_rate().0
+        );
 
         println!("Beginning Playback...");
 
-        type StateHandle = Arc<Mutex<Option<(usize, Vec<f32>, Sender<()>)>>>;
         let sample_rate = config.sample_rate().0;
-        let (done_tx, done_rx) = channel::<()>();
-        let state = (0, self.resample(sample_rate).samples, done_tx);
-        let state = Arc::new(Mutex::new(Some(state)));
         let channels = config.channels();
+        let samples = self
+            .resample_with(sample_rate, quality)
+            .map_channels(channels)
+            .samples;
+
+        let state = Arc::new(PlayState::default());
+        let state_2 = state.clone();
+        let (mut samples_tx, mut samples_rx) =
+            RingBuffer::new(ring_len(PLAY_RING, sample_rate, channels));
 
         let err_fn = move |err| {
             eprintln!("an error occurred on stream: {}", err);
         };
 
-        fn write_output_data<T>(output: &mut [T], channels: u16, writer: &StateHandle)
-        where
-            T: Sample + cpal::FromSample<f32>,
-        {
-            if let Ok(mut guard) = writer.try_lock() {
-                if let Some((i, clip_samples, done)) = guard.as_mut() {
-                    for frame in output.chunks_mut(channels.into()) {
-                        for sample in frame.iter_mut() {
-                            *sample = Sample::from_sample(
-                                clip_samples.get(*i).unwrap_or(&0f32).to_owned(),
-                            );
-                        }
-                        *i += 1;
-                    }
-
-                    if *i >= clip_samples.len() && done.send(()).is_err() {
-                        // Playback has already ended. We will be dead soon.
-                    }
-                }
-            }
-        }
+        // full before the stream asks for anything
+        let mut fed = 0;
+        stream::feed(&samples, &mut fed, &mut samples_tx, &state);
 
         let stream = match config.sample_format() {
             cpal::SampleFormat::F32 => device.build_output_stream(
                 &config.into(),
-                move |data, _: &_| write_output_data::<f32>(data, channels, &state),
+                move |data, _: &_| write_output_data::<f32>(data, &mut samples_rx, &state_2),
                 err_fn,
                 None,
             )?,
 
             cpal::SampleFormat::I16 => device.build_output_stream(
                 &config.into(),
-                move |data, _: &_| write_output_data::<i16>(data, channels, &state),
+                move |data, _: &_| write_output_data::<i16>(data, &mut samples_rx, &state_2),
                 err_fn,
                 None,
             )?,
 
             cpal::SampleFormat::U16 => device.build_output_stream(
                 &config.into(),
-                move |data, _: &_| write_output_data::<u16>(data, channels, &state),
+                move |data, _: &_| write_output_data::<u16>(data, &mut samples_rx, &state_2),
                 err_fn,
                 None,
             )?,
 
-            _ => return Err(eyre!("Unsupported Sample Format")), // maybe deal with this somewhen
+            _ => return Err(Error::UnsupportedSampleFormat), // maybe deal with this somewhen
         };
         stream.play()?;
 
-        done_rx.recv()?;
+        while !state.done.load(Ordering::Acquire) {
+            std::thread::sleep(FEED_INTERVAL);
+            stream::feed(&samples, &mut fed, &mut samples_tx, &state);
+        }
+
+        println!(
+            "Playback finished, {} underrun(s)",
+            state.underruns.load(Ordering::Relaxed)
+        );
         Ok(())
     }
 
-    pub fn resample(&self, sample_rate: u32) -> AudioClip {
-        if self.sample_rate == sample_rate {
+    /// The clip as mono, with its channels combined the way `downmix` says.
+    /// Mono clips are returned as they are
+    pub fn downmix(&self, downmix: Downmix) -> Result<AudioClip> {
+        if self.channels <= 1 {
+            return Ok(self.clone());
+        }
+
+        let channel = match downmix {
+            Downmix::Average => return Ok(self.to_mono()),
+            Downmix::Left => 0,
+            Downmix::Right => 1,
+            Downmix::MaxEnergy => self.loudest_channel(),
+            Downmix::Channel(channel) => channel,
+        };
+
+        if channel >= self.channels as usize {
+            return Err(Error::NoSuchChannel(channel, self.channels));
+        }
+
+        Ok(AudioClip {
+            samples: self
+                .samples
+                .iter()
+                .skip(channel)
+                .step_by(self.channels as usize)
+                .copied()
+                .collect(),
+            channels: 1,
+            ..self.clone()
+        })
+    }
+
+    // Index of the channel with the largest sum of squares
+    fn loudest_channel(&self) -> usize {
+        let channels = self.channels.max(1) as usize;
+        let mut energy = vec![0f64; channels];
+
+        for frame in self.samples.chunks(channels) {
+            for (channel, sample) in frame.iter().enumerate() {
+                energy[channel] += (*sample as f64).powi(2);
+            }
+        }
+
+        energy
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(channel, _)| channel)
+            .unwrap_or(0)
+    }
+
+    /// The clip with only its first `channels` channels
+    pub fn first_channels(&self, channels: u16) -> AudioClip {
+        if self.channels <= channels {
             return self.clone();
         }
 
-        let mut signal = signal::from_iter(self.samples.iter().copied());
-        let a = signal.next();
-        let b = signal.next();
+        AudioClip {
+            samples: self
+                .samples
+                .chunks(self.channels as usize)
+                .flat_map(|frame| &frame[..channels as usize])
+                .copied()
+                .collect(),
+            channels,
+            ..self.clone()
+        }
+    }
+
+    /// The clip with `channels` channels, for playing it on a device with that many.
+    ///
+    /// Mono is copied to every channel, everything goes into a mono device averaged,
+    /// otherwise the channels are matched up and any extra device channels stay silent
+    pub fn map_channels(&self, channels: u16) -> AudioClip {
+        if self.channels == channels {
+            return self.clone();
+        }
+
+        if channels == 1 {
+            return self.to_mono();
+        }
+
+        let from = self.channels.max(1) as usize;
+        let to = channels as usize;
+        let mut samples = Vec::with_capacity(self.frames() * to);
 
-        let linear = Linear::new(a, b);
+        for frame in self.samples.chunks(from) {
+            for channel in 0..to {
+                samples.push(match from {
+                    1 => frame[0],
+                    _ => frame.get(channel).copied().unwrap_or(0.0),
+                });
+            }
+        }
+
+        AudioClip {
+            samples,
+            channels,
+            ..self.clone()
+        }
+    }
+
+    /// The part of the clip from `from` up to `to`, or up to the end without one.
+    /// A `to` past the end is the end
+    pub fn range(&self, from: Duration, to: Option<Duration>) -> Result<AudioClip> {
+        let frame_at = |time: Duration| (time.as_secs_f64() * self.sample_rate as f64).round();
+        let frames = self.frames();
+
+        let start = frame_at(from);
+        let end = to.map_or(frames as f64, frame_at).min(frames as f64);
+
+        if start >= frames as f64 {
+            return Err(Error::Export(format!(
+                "the range starts at {:.2}s, but the clip is only {:.2}s long",
+                from.as_secs_f64(),
+                frames as f64 / self.sample_rate as f64
+            )));
+        }
+        if end <= start {
+            return Err(Error::Export(format!(
+                "the range ends at {:.2}s, before it starts at {:.2}s",
+                to.unwrap_or_default().as_secs_f64(),
+                from.as_secs_f64()
+            )));
+        }
+
+        let channels = self.channels.max(1) as usize;
+        Ok(AudioClip {
+            samples: self.samples[start as usize * channels..end as usize * channels].to_vec(),
+            ..self.clone()
+        })
+    }
+
+    /// Joins the clips one after the other with `gap` of silence between them.
+    /// They are all brought to the highest sample rate and channel count among them,
+    /// the joined clip takes the name and date of the first one
+    pub fn concat(
+        clips: &[AudioClip],
+        gap: Duration,
+        quality: ResampleQuality,
+    ) -> Result<AudioClip> {
+        let first = clips
+            .first()
+            .ok_or_else(|| Error::Export("there are no clips to join".to_string()))?;
+        let sample_rate = clips
+            .iter()
+            .map(|clip| clip.sample_rate)
+            .max()
+            .unwrap_or(48000);
+        let channels = clips.iter().map(|clip| clip.channels).max().unwrap_or(1);
+
+        let gap_frames = (gap.as_secs_f64() * sample_rate as f64).round() as usize;
+        let mut samples = Vec::new();
+
+        for (i, clip) in clips.iter().enumerate() {
+            if i > 0 {
+                samples.resize(samples.len() + gap_frames * channels as usize, 0.0);
+            }
+
+            let clip = clip
+                .resample_with(sample_rate, quality)
+                .map_channels(channels);
+            samples.extend_from_slice(&clip.samples);
+        }
+
+        Ok(AudioClip::new(
+            sample_rate,
+            channels,
+            samples,
+            None,
+            first.name.clone(),
+            first.date,
+        ))
+    }
+
+    // Resamples with the default `ResampleQuality`
+    pub fn resample(&self, sample_rate: u32) -> AudioClip {
+        self.resample_with(sample_rate, ResampleQuality::default())
+    }
+
+    pub fn resample_with(&self, sample_rate: u32, quality: ResampleQuality) -> AudioClip {
+        if self.sample_rate == sample_rate {
+            return self.clone();
+        }
 
         AudioClip {
             id: self.id,
             name: self.name.clone(),
             date: self.date,
-            samples: signal
-                .from_hz_to_hz(linear, self.sample_rate as f64, sample_rate as f64)
-                .take(self.samples.len() * (sample_rate as usize) / self.sample_rate as usize)
-                .collect(),
+            samples: resample::resample(
+                &self.samples,
+                self.channels,
+                self.sample_rate,
+                sample_rate,
+                quality,
+            ),
 
             sample_rate,
+            channels: self.channels,
         }
     }
 
+    // Imports the first track of any file symphonia can read with all of its channels.
+    // Anything over two channels has to go through `downmix` or `first_channels` before it can be saved
     pub fn import(name: String, path: String) -> Result<AudioClip> {
+        Ok(Self::import_with_metadata(name, path)?.0)
+    }
+
+    /// Like `import`, along with whatever the tags of the file say about the clip.
+    /// The clip gets the date from the tags if there is one, and the date the file was created otherwise
+    pub fn import_with_metadata(name: String, path: String) -> Result<(AudioClip, Metadata)> {
         // Create a media source.
         // MediaSource trait is automatically implemented for File
         let file = Box::new(File::open(Path::new(&path))?);
@@ -261,21 +760,30 @@ impl AudioClip {
         let decoder_opts: DecoderOptions = Default::default();
 
         // Probe the media source stream for a format.
-        let probed =
+        let mut probed =
             symphonia::default::get_probe().format(&hint, mss, &format_opts, &metadata_opts)?;
 
         // Get the format reader yielded by the probe operation.
         let mut format = probed.format;
 
+        // Tags can be in front of the container (like ID3) or inside it
+        let mut tags = Vec::new();
+        if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+            tags.extend_from_slice(revision.tags());
+        }
+        if let Some(revision) = format.metadata().current() {
+            tags.extend_from_slice(revision.tags());
+        }
+        let metadata = Metadata::from_tags(&tags);
+
         // Get the default track.
         let track = format
             .default_track()
-            .ok_or_else(|| eyre!("No default track"))?;
+            .ok_or_else(|| Error::Import("No default track".to_string()))?;
 
         // Create a decoder for the track.
         let mut decoder = symphonia::default::get_codecs()
-            .make(&track.codec_params, &decoder_opts)
-            .unwrap();
+            .make(&track.codec_params, &decoder_opts)?;
 
         // Store the track identifier, we'll use it to filter packets.
         let track_id = track.id;
@@ -285,17 +793,18 @@ impl AudioClip {
         let channels = track
             .codec_params
             .channels
-            .ok_or_else(|| eyre!("Unknown Number of Channels"))?;
+            .ok_or_else(|| Error::Import("Unknown Number of Channels".to_string()))?;
 
         let mut clip = AudioClip::new(
             track
                 .codec_params
                 .sample_rate
-                .ok_or_else(|| eyre!("Unknown Sample Rate"))?,
+                .ok_or_else(|| Error::Import("Unknown Sample Rate".to_string()))?,
+            channels.count().try_into()?,
             Vec::new(),
             None,
             name,
-            creation_time,
+            metadata.date.unwrap_or(creation_time),
         );
 
         loop {
@@ -304,7 +813,7 @@ impl AudioClip {
             let packet = match format.next_packet() {
                 Ok(packet_ok) => packet_ok,
 
-                Err(Error::IoError(ref packet_err))
+                Err(SymphoniaError::IoError(ref packet_err))
                     if packet_err.kind() == std::io::ErrorKind::UnexpectedEof =>
                 {
                     break;
@@ -347,14 +856,7 @@ impl AudioClip {
                     // Copy the decoded audio buffer into the sample buffer in an interleaved format.
                     if let Some(buf) = &mut sample_buf {
                         buf.copy_interleaved_ref(audio_buf);
-                        let mono: Vec<f32> = buf
-                            .samples()
-                            .iter()
-                            .step_by(channels.count())
-                            .copied()
-                            .collect();
-
-                        clip.samples.extend_from_slice(&mono);
+                        clip.samples.extend_from_slice(buf.samples());
 
                         // The samples may now be access via the `samples()` function.
                         sample_count += buf.samples().len();
@@ -362,37 +864,61 @@ impl AudioClip {
                     }
                 }
 
-                Err(Error::DecodeError(_)) => (),
+                Err(SymphoniaError::DecodeError(_)) => (),
 
                 Err(_) => break,
             }
         }
 
-        Ok(clip)
+        Ok((clip, metadata))
     }
 
+    /// Writes the clip to `path`, as wav or FLAC depending on the extension,
+    /// tagged with its name and date
     pub fn export(&self, path: &str) -> Result<()> {
-        if !path.ends_with(".wav") {
-            return Err(eyre!(
-                "Expected the path to end with `.wav`.\nPath given : {}",
+        if path.ends_with(".wav") {
+            self.export_wav(path, &WavSettings::default(), &Metadata::from(self))
+        } else if path.ends_with(".flac") {
+            self.export_flac(path, &FlacSettings::default(), &Metadata::from(self))
+        } else {
+            Err(Error::InvalidPath(format!(
+                "Expected the path to end with `.wav` or `.flac`.\nPath given : {}",
                 path
-            ));
-        }
-        let spec = hound::WavSpec {
-            channels: 1,
-            sample_rate: self.sample_rate,
-            bits_per_sample: 32,
-            sample_format: hound::SampleFormat::Float,
-        };
-
-        let mut writer = hound::WavWriter::create(path, spec)?;
-
-        for sample in &self.samples {
-            writer.write_sample(*sample)?;
+            )))
         }
+    }
 
-        writer.finalize()?;
+    /// Writes the clip to `path` as a wav file, whatever the path ends with
+    pub fn export_wav(
+        &self,
+        path: &str,
+        settings: &WavSettings,
+        metadata: &Metadata,
+    ) -> Result<()> {
+        wav::write(
+            path,
+            &self.samples,
+            self.channels,
+            self.sample_rate,
+            settings,
+            metadata,
+        )
+    }
 
-        Ok(())
+    /// Writes the clip to `path` as a FLAC file, whatever the path ends with
+    pub fn export_flac(
+        &self,
+        path: &str,
+        settings: &FlacSettings,
+        metadata: &Metadata,
+    ) -> Result<()> {
+        flac::write(
+            path,
+            &self.samples,
+            self.channels,
+            self.sample_rate,
+            settings,
+            metadata,
+        )
     }
 }
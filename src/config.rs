@@ -0,0 +1,76 @@
+#![allow(non_snake_case)]
+//! Settings kept between runs, in a toml file.
+//!
+//! ```toml
+//! [record]
+//! device = "USB Microphone"
+//! rate = 48000
+//!
+//! [play]
+//! host = "JACK"
+//! device = "1"
+//! ```
+
+use crate::device::DeviceChoice;
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Environment variable that overrides the default config file location
+pub const CONFIG_ENV_VAR: &str = "OXYGEN_CONFIG";
+
+/// Everything the config file can hold, all of it optional
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The device `record` uses
+    pub record: DeviceChoice,
+    /// The device `play` and `play-last` use
+    pub play: DeviceChoice,
+}
+
+impl Config {
+    /// Where the config file lives when no path is given.
+    ///
+    /// `$OXYGEN_CONFIG` wins if it is set, otherwise this is `oxygen/config.toml`
+    /// inside the platform config directory (`$XDG_CONFIG_HOME`, or `~/.config` on linux)
+    pub fn default_path() -> PathBuf {
+        if let Some(path) = std::env::var_os(CONFIG_ENV_VAR).filter(|p| !p.is_empty()) {
+            return PathBuf::from(path);
+        }
+
+        match dirs::config_dir() {
+            Some(dir) => dir.join("oxygen").join("config.toml"),
+            None => PathBuf::from("oxygen.toml"),
+        }
+    }
+
+    /// Reads the config file at `path`, a missing file is an empty config
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err.into()),
+        };
+
+        toml::from_str(&text)
+            .map_err(|err| Error::Config(path.display().to_string(), err.to_string()))
+    }
+
+    /// Writes the config to `path`, creating the folder it goes in
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let text = toml::to_string(self)
+            .map_err(|err| Error::Config(path.display().to_string(), err.to_string()))?;
+        std::fs::write(path, text)?;
+
+        Ok(())
+    }
+}
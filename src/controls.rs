@@ -0,0 +1,79 @@
+#![allow(non_snake_case)]
+//! The keys that control a recording, read straight from the terminal.
+//!
+//! The terminal is put in raw mode so keys arrive as they are pressed, without Enter.
+//! In raw mode a newline doesn't go back to the start of the line, so anything printed
+//! meanwhile goes through `status`.
+
+use crate::error::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use std::io::{IsTerminal, Write};
+use std::time::Duration;
+
+/// What a key press asks the recording to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    /// Space
+    PauseResume,
+    /// `m`
+    Marker,
+    /// `q`, Enter, Esc or Ctrl-C
+    Stop,
+}
+
+/// The terminal in raw mode, back to normal once dropped
+pub struct RawTerminal(());
+
+impl RawTerminal {
+    /// Raw mode, if stdin is a terminal that allows it
+    pub fn enable() -> Option<Self> {
+        if !std::io::stdin().is_terminal() {
+            return None;
+        }
+
+        terminal::enable_raw_mode().ok()?;
+        Some(RawTerminal(()))
+    }
+
+    /// The next key that means something, if one is pressed within `timeout`.
+    /// Other keys are skipped
+    pub fn poll(&self, timeout: Duration) -> Result<Option<Control>> {
+        if !event::poll(timeout)? {
+            return Ok(None);
+        }
+
+        let Event::Key(key) = event::read()? else {
+            return Ok(None);
+        };
+        // windows reports releasing the key as well
+        if key.kind != KeyEventKind::Press {
+            return Ok(None);
+        }
+
+        Ok(match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Control::Stop)
+            }
+            KeyCode::Char(' ') => Some(Control::PauseResume),
+            KeyCode::Char('m') | KeyCode::Char('M') => Some(Control::Marker),
+            KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Enter | KeyCode::Esc => {
+                Some(Control::Stop)
+            }
+            _ => None,
+        })
+    }
+
+    /// Prints a line while in raw mode
+    pub fn status(&self, line: &str) {
+        let mut stdout = std::io::stdout();
+        let _ = write!(stdout, "{}\r\n", line);
+        let _ = stdout.flush();
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
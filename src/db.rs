@@ -1,9 +1,26 @@
 #![allow(non_snake_case)]
-use crate::audio_clip::AudioClip;
-use crate::internal_encoding::{decode_v0, decode_v1, encode_v1};
+use crate::audio_clip::{AudioClip, Marker};
+use crate::error::{Error, Result};
+use crate::internal_encoding::{
+    BitrateMode, CODEC_VERSION, EncoderSettings, HeaderV2, application_from_name, application_name,
+    decode, decode_v0, encode_v1, encode_v2, sample_count_v1,
+};
+use crate::metadata::Metadata;
+use crate::transcribe::{Segment, Transcript};
 use chrono::prelude::*;
-use color_eyre::eyre::Result;
-use rusqlite::{Connection, params, types::Type};
+use rusqlite::{
+    Connection, Row, params, params_from_iter,
+    types::{Type, Value},
+};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Environment variable that overrides the default database location
+pub const DB_ENV_VAR: &str = "OXYGEN_DB";
+
+// where journals used to live before the location became configurable
+const LEGACY_DB_PATH: &str = "./data/oxygen.sqlite";
 
 pub struct Db(Connection);
 
@@ -14,42 +31,256 @@ pub struct Db(Connection);
     * so maybe sending a different struct is better, that some how will hold the name of the clips.
 
     * this struct just contains the metadata for the clip.
+    * when serialized the `clip_` prefix is dropped, so `clip_name` becomes `name`
 */
+#[derive(Serialize)]
 pub struct ClipMeta {
+    #[serde(rename = "id")]
     pub clip_id: usize,
+    #[serde(rename = "name")]
     pub clip_name: String,
+    #[serde(rename = "date")]
     pub clip_date: DateTime<Utc>,
+    #[serde(rename = "tags")]
+    pub clip_tags: Vec<String>,
+    #[serde(rename = "notes")]
+    pub clip_notes: Option<String>,
+    // sample rate of the stored audio, which is not always the rate it was recorded at
+    #[serde(rename = "sample_rate")]
+    pub clip_sample_rate: u32,
+    #[serde(rename = "channels")]
+    pub clip_channels: u16,
+    // samples in every channel
+    #[serde(rename = "sample_count")]
+    pub clip_sample_count: u64,
+    // size of the encoded audio in bytes
+    #[serde(rename = "size")]
+    pub clip_size: u64,
+    #[serde(rename = "codec_version")]
+    pub clip_codec_version: u32,
+    #[serde(rename = "encoder")]
+    pub clip_encoder: EncoderSettings,
+}
+
+impl ClipMeta {
+    pub fn duration(&self) -> Duration {
+        if self.clip_sample_rate == 0 {
+            return Duration::ZERO;
+        }
+
+        Duration::from_secs_f64(self.clip_sample_count as f64 / self.clip_sample_rate as f64)
+    }
+}
+
+// A clip that matched a `search`
+#[derive(Serialize)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    pub meta: ClipMeta,
+    // the part of the name, notes or tags that matched, with the matches in `[]`
+    pub snippet: String,
+    // bm25 score of the match, lower is better
+    pub rank: f64,
+}
+
+// A clip sitting in the trash
+#[derive(Serialize)]
+pub struct TrashedClip {
+    #[serde(flatten)]
+    pub meta: ClipMeta,
+    pub deleted_at: DateTime<Utc>,
+}
+
+// The columns `clip_meta_from_row` expects, selected from `clips c`
+const CLIP_META_COLUMNS: &str = "
+    c.id, c.name, c.date, c.notes,
+    (
+        SELECT GROUP_CONCAT(t.name, ',')
+        FROM clip_tags ct JOIN tags t ON t.id = ct.tag_id
+        WHERE ct.clip_id = c.id
+    ),
+    c.sample_rate, c.sample_count, c.byte_size, c.codec_version,
+    c.bitrate, c.frame_ms, c.complexity, c.bitrate_mode, c.application, c.channels";
+
+// so queries selecting more than `CLIP_META_COLUMNS` know where their own columns start
+const CLIP_META_COLUMN_COUNT: usize = 15;
+
+// Builds a `ClipMeta` from a row of `CLIP_META_COLUMNS`
+fn clip_meta_from_row(row: &Row) -> rusqlite::Result<ClipMeta> {
+    let _date: String = row.get(2)?; // we need to convert this into a `DateTime` type
+    let tags: Option<String> = row.get(4)?;
+
+    let mut clip_tags: Vec<String> = tags
+        .map(|tags| tags.split(',').map(str::to_string).collect())
+        .unwrap_or_default();
+    clip_tags.sort();
+
+    let bitrate_mode: String = row.get(12)?;
+    let application: String = row.get(13)?;
+
+    Ok(ClipMeta {
+        clip_id: row.get(0)?,
+        clip_name: row.get(1)?,
+        clip_date: _date.parse().map_err(|_| {
+            rusqlite::Error::InvalidColumnType(2, "date".to_string(), Type::Text)
+        })?,
+        clip_tags,
+        clip_notes: row.get(3)?,
+        clip_sample_rate: row.get(5)?,
+        clip_channels: row.get(14)?,
+        clip_sample_count: row.get(6)?,
+        clip_size: row.get(7)?,
+        clip_codec_version: row.get(8)?,
+        clip_encoder: EncoderSettings {
+            bitrate: row.get(9)?,
+            frame_ms: row.get(10)?,
+            complexity: row.get(11)?,
+            bitrate_mode: BitrateMode::from_name(&bitrate_mode).ok_or_else(|| {
+                rusqlite::Error::InvalidColumnType(12, "bitrate_mode".to_string(), Type::Text)
+            })?,
+            application: application_from_name(&application).ok_or_else(|| {
+                rusqlite::Error::InvalidColumnType(13, "application".to_string(), Type::Text)
+            })?,
+        },
+    })
+}
+
+// Tags are stored as they are given (minus surrounding whitespace),
+// but they can't be empty and can't contain a `,` since that is what we join them with
+fn validate_tag(tag: &str) -> Result<&str> {
+    let tag = tag.trim();
+
+    if tag.is_empty() || tag.contains(',') {
+        return Err(Error::InvalidTag(tag.to_string()));
+    }
+
+    Ok(tag)
+}
+
+// Turns what was typed into `search` into an FTS5 query.
+// Every word is quoted so `-`, `:`, `'` and the like are searched for instead of being
+// taken as FTS5 syntax (a date like 2026-10-18 would be column filters otherwise).
+// `"phrases"` stay phrases, and a `*` at the end of a word or phrase still makes it a prefix
+fn fts_query(query: &str) -> String {
+    // quotes inside a string are doubled
+    let quoted = |term: &str| format!("\"{}\"", term.replace('"', "\"\""));
+
+    let mut terms = Vec::new();
+    let mut rest = query.trim_start();
+
+    while !rest.is_empty() {
+        let (term, after) = if let Some(phrase) = rest.strip_prefix('"') {
+            // a phrase that isn't closed runs to the end
+            let end = phrase.find('"').unwrap_or(phrase.len());
+            let after = phrase[end..].strip_prefix('"').unwrap_or("");
+            if phrase[..end].trim().is_empty() {
+                (String::new(), after)
+            } else {
+                (quoted(&phrase[..end]), after)
+            }
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '"')
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            let prefix = word.trim_end_matches('*');
+            if prefix.is_empty() {
+                // a lone `*` isn't a word
+                (String::new(), &rest[end..])
+            } else if prefix.len() < word.len() {
+                (format!("{}*", quoted(prefix)), &rest[end..])
+            } else {
+                (quoted(word), &rest[end..])
+            }
+        };
+
+        // a phrase can be a prefix too
+        let (term, after) = match after.strip_prefix('*') {
+            Some(after) if term.ends_with('"') => (format!("{}*", term), after),
+            _ => (term, after),
+        };
+
+        if !term.is_empty() {
+            terms.push(term);
+        }
+        rest = after.trim_start_matches('*').trim_start();
+    }
+
+    terms.join(" ")
 }
 
 // Checks if a specified file exists or not
-fn init_file_structure(path: &str) {
-    let flag = std::path::Path::new(path).exists();
+fn init_file_structure(path: &Path) -> Result<()> {
+    let flag = path.exists();
 
     if !flag {
         // create a directory
-        std::fs::create_dir_all("data").expect("Failed to create directory");
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
         // create a file
-        std::fs::File::create(path).unwrap();
+        std::fs::File::create(path)?;
     }
+
+    Ok(())
+}
+
+// Runs one step of bringing the schema up to date and sets the version to `version`,
+// in one transaction so a step that fails halfway is tried again on the next open
+fn migrate(
+    connection: &mut Connection,
+    version: u32,
+    step: impl FnOnce(&Connection) -> Result<()>,
+) -> Result<()> {
+    let tx = connection.transaction()?;
+    step(&tx)?;
+    tx.pragma_update(None, "user_version", version)?;
+    tx.commit()?;
+
+    Ok(())
 }
 
 impl Db {
-    // Connection function that connects to an sqlite database file
+    /// Where the journal lives when no path is given.
+    ///
+    /// `$OXYGEN_DB` wins if it is set, otherwise this is `oxygen/oxygen.sqlite`
+    /// inside the platform data directory (`$XDG_DATA_HOME`, or `~/.local/share` on linux)
+    pub fn default_path() -> PathBuf {
+        if let Some(path) = std::env::var_os(DB_ENV_VAR).filter(|p| !p.is_empty()) {
+            return PathBuf::from(path);
+        }
+
+        match dirs::data_dir() {
+            Some(dir) => dir.join("oxygen").join("oxygen.sqlite"),
+            // no home directory, fall back to the old behaviour
+            None => PathBuf::from(LEGACY_DB_PATH),
+        }
+    }
+
+    // Connection function that connects to the sqlite database file at the default location
     pub fn open() -> Result<Self> {
-        // the sqlitefile will be stored in a directory named "data" up the src dir
-        // if we consider the binary is in a directory called "bin" and
-        // there is also a directory called "data"
-        // then the sqlite file will be stored in "data/db.sqlite"
-        /*
-            Directory structure:
-            Oxygen
-            |-bin
-            |-data
-        */
+        let path = Self::default_path();
+
+        let legacy = Path::new(LEGACY_DB_PATH);
+        if !path.exists() && legacy.exists() {
+            eprintln!(
+                "Found a journal at {} but Oxygen now uses {}.\nMove the file there (or pass `--db {}`) to keep using it",
+                legacy.display(),
+                path.display(),
+                legacy.display()
+            );
+        }
+
+        Self::open_at(path)
+    }
 
-        init_file_structure("./data/oxygen.sqlite");
+    // Connects to (and if needed creates and migrates) the sqlite database file at `path`
+    pub fn open_at(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
 
-        let connection = Connection::open("./data/oxygen.sqlite")?;
+        init_file_structure(path)?;
+
+        let mut connection = Connection::open(path)?;
 
         let user_version: u32 =
             connection.query_row("SELECT user_version FROM pragma_user_version", [], |r| {
@@ -57,118 +288,430 @@ impl Db {
             })?;
 
         connection.pragma_update(None, "page_size", 8192)?;
-        connection.pragma_update(None, "user_version", 2)?;
+        connection.pragma_update(None, "foreign_keys", true)?;
 
         if user_version < 1 {
             eprintln!("Initalizing database");
-            connection.execute(
-                "
-                CREATE TABLE IF NOT EXISTS clips
-                (
-                    id INTEGER PRIMARY KEY,
-                    name TEXT NOT NULL UNIQUE,
-                    date TEXT NOT NULL,
-                    sample_rate INTEGER NOT NULL,
-                    samples BLOB NOT NULL
-                );
-                ",
-                [],
-            )?;
+            migrate(&mut connection, 1, |connection| {
+                connection.execute(
+                    "
+                    CREATE TABLE IF NOT EXISTS clips
+                    (
+                        id INTEGER PRIMARY KEY,
+                        name TEXT NOT NULL UNIQUE,
+                        date TEXT NOT NULL,
+                        sample_rate INTEGER NOT NULL,
+                        samples BLOB NOT NULL
+                    );
+                    ",
+                    [],
+                )?;
+                Ok(())
+            })?;
         }
 
         if user_version < 2 {
             eprintln!("Updating database to version 2...");
-            let mut stmt = connection.prepare(
-                "
-                SELECT id, name, date, sample_rate, samples
-                FROM clips
-                ",
-            )?;
+            migrate(&mut connection, 2, |connection| {
+                let mut stmt = connection.prepare(
+                    "
+                    SELECT id, name, date, sample_rate, samples
+                    FROM clips
+                    ",
+                )?;
 
-            let clip_iter = stmt.query_map([], |row| {
-                let _date: String = row.get(2)?; // we need to convert this into a `DateTime` type
-                let samples: Vec<u8> = row.get(4)?;
-
-                Ok(AudioClip {
-                    id: Some(row.get(0)?),
-                    name: row.get(1)?,
-                    date: _date.parse().map_err(|_| {
-                        rusqlite::Error::InvalidColumnType(2, "date".to_string(), Type::Text)
-                    })?,
-                    sample_rate: row.get(3)?,
-                    samples: decode_v0(&samples),
-                })
+                let clip_iter = stmt.query_map([], |row| {
+                    let _date: String = row.get(2)?; // we need to convert this into a `DateTime` type
+                    let samples: Vec<u8> = row.get(4)?;
+
+                    Ok(AudioClip {
+                        id: Some(row.get(0)?),
+                        name: row.get(1)?,
+                        date: _date.parse().map_err(|_| {
+                            rusqlite::Error::InvalidColumnType(2, "date".to_string(), Type::Text)
+                        })?,
+                        sample_rate: row.get(3)?,
+                        channels: 1,
+                        samples: decode_v0(&samples),
+                    })
+                })?;
+
+                let clips: Vec<_> = clip_iter.collect::<Result<_, rusqlite::Error>>()?;
+
+                for clip in &clips {
+                    let (sr, bytes) = encode_v1(clip)?;
+
+                    connection.execute(
+                        "
+                        INSERT OR REPLACE INTO clips (id, name, date, sample_rate, samples)
+                        VALUES (?1, ?2, ?3, ?4, ?5)",
+                        params![clip.id, clip.name, clip.date.to_string(), sr, bytes],
+                    )?;
+                }
+
+                connection.execute("ALTER TABLE clips RENAME COLUMN samples TO opus", [])?;
+                Ok(())
             })?;
+        }
 
-            let clips: Vec<_> = clip_iter.collect::<Result<_, rusqlite::Error>>()?;
+        if user_version < 3 {
+            eprintln!("Updating database to version 3...");
+            migrate(&mut connection, 3, |connection| {
+                connection.execute_batch(
+                    "
+                    CREATE TABLE IF NOT EXISTS tags
+                    (
+                        id INTEGER PRIMARY KEY,
+                        name TEXT NOT NULL UNIQUE
+                    );
 
-            for clip in &clips {
-                let (sr, bytes) = encode_v1(clip)?;
+                    CREATE TABLE IF NOT EXISTS clip_tags
+                    (
+                        clip_id INTEGER NOT NULL REFERENCES clips(id) ON DELETE CASCADE,
+                        tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+                        PRIMARY KEY (clip_id, tag_id)
+                    );
+                    ",
+                )?;
+                Ok(())
+            })?;
+        }
 
+        if user_version < 4 {
+            eprintln!("Updating database to version 4...");
+            migrate(&mut connection, 4, |connection| {
+                connection.execute("ALTER TABLE clips ADD COLUMN notes TEXT", [])?;
+                Ok(())
+            })?;
+        }
+
+        if user_version < 5 {
+            eprintln!("Updating database to version 5...");
+            migrate(&mut connection, 5, |connection| {
+                // the search index, its rowid is the id of the clip.
+                // The triggers keep it in sync so nothing else has to care about it
+                connection.execute_batch(
+                    "
+                    CREATE VIRTUAL TABLE IF NOT EXISTS clips_fts USING fts5
+                    (
+                        name,
+                        notes,
+                        tags,
+                        prefix = '2 3'
+                    );
+
+                    CREATE TRIGGER IF NOT EXISTS clips_fts_insert AFTER INSERT ON clips
+                    BEGIN
+                        INSERT INTO clips_fts (rowid, name, notes, tags)
+                        VALUES (new.id, new.name, new.notes, '');
+                    END;
+
+                    CREATE TRIGGER IF NOT EXISTS clips_fts_update AFTER UPDATE OF name, notes ON clips
+                    BEGIN
+                        UPDATE clips_fts SET name = new.name, notes = new.notes
+                        WHERE rowid = new.id;
+                    END;
+
+                    CREATE TRIGGER IF NOT EXISTS clips_fts_delete AFTER DELETE ON clips
+                    BEGIN
+                        DELETE FROM clips_fts WHERE rowid = old.id;
+                    END;
+
+                    CREATE TRIGGER IF NOT EXISTS clips_fts_tag_insert AFTER INSERT ON clip_tags
+                    BEGIN
+                        UPDATE clips_fts SET tags = (
+                            SELECT GROUP_CONCAT(t.name, ' ')
+                            FROM clip_tags ct JOIN tags t ON t.id = ct.tag_id
+                            WHERE ct.clip_id = new.clip_id
+                        )
+                        WHERE rowid = new.clip_id;
+                    END;
+
+                    CREATE TRIGGER IF NOT EXISTS clips_fts_tag_delete AFTER DELETE ON clip_tags
+                    BEGIN
+                        UPDATE clips_fts SET tags = (
+                            SELECT GROUP_CONCAT(t.name, ' ')
+                            FROM clip_tags ct JOIN tags t ON t.id = ct.tag_id
+                            WHERE ct.clip_id = old.clip_id
+                        )
+                        WHERE rowid = old.clip_id;
+                    END;
+
+                    INSERT INTO clips_fts (rowid, name, notes, tags)
+                    SELECT c.id, c.name, c.notes,
+                        (
+                            SELECT GROUP_CONCAT(t.name, ' ')
+                            FROM clip_tags ct JOIN tags t ON t.id = ct.tag_id
+                            WHERE ct.clip_id = c.id
+                        )
+                    FROM clips c;
+                    ",
+                )?;
+                Ok(())
+            })?;
+        }
+
+        if user_version < 6 {
+            eprintln!("Updating database to version 6...");
+            migrate(&mut connection, 6, |connection| {
                 connection.execute(
                     "
-                    INSERT OR REPLACE INTO clips (id, name, date, sample_rate, samples)
-                    VALUES (?1, ?2, ?3, ?4, ?5)",
-                    params![clip.id, clip.name, clip.date.to_string(), sr, bytes],
+                    CREATE TABLE IF NOT EXISTS transcripts
+                    (
+                        clip_id INTEGER NOT NULL REFERENCES clips(id) ON DELETE CASCADE,
+                        segment INTEGER NOT NULL,
+                        start_ms INTEGER NOT NULL,
+                        end_ms INTEGER NOT NULL,
+                        text TEXT NOT NULL,
+                        PRIMARY KEY (clip_id, segment)
+                    );
+                    ",
+                    [],
                 )?;
-            }
+                Ok(())
+            })?;
+        }
+
+        if user_version < 7 {
+            eprintln!("Updating database to version 7...");
+            migrate(&mut connection, 7, |connection| {
+                // unix timestamp of when the clip was moved to the trash, NULL if it is not in the trash
+                connection.execute("ALTER TABLE clips ADD COLUMN deleted_at INTEGER", [])?;
+                Ok(())
+            })?;
+        }
 
-            connection.execute("ALTER TABLE clips RENAME COLUMN samples TO opus", [])?;
+        if user_version < 8 {
+            eprintln!("Updating database to version 8...");
+            migrate(&mut connection, 8, |connection| {
+                connection.execute_batch(
+                    "
+                    ALTER TABLE clips ADD COLUMN sample_count INTEGER NOT NULL DEFAULT 0;
+                    ALTER TABLE clips ADD COLUMN byte_size INTEGER NOT NULL DEFAULT 0;
+                    ALTER TABLE clips ADD COLUMN codec_version INTEGER NOT NULL DEFAULT 1;
+                    ",
+                )?;
+
+                // everything stored so far is v1, so the sample count is in the header
+                let mut stmt = connection.prepare("SELECT id, opus FROM clips")?;
+                let rows = stmt
+                    .query_map([], |row| {
+                        Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?))
+                    })?
+                    .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+
+                for (id, bytes) in rows {
+                    connection.execute(
+                        "UPDATE clips SET sample_count = ?1, byte_size = ?2 WHERE id = ?3",
+                        params![sample_count_v1(&bytes).unwrap_or(0), bytes.len(), id],
+                    )?;
+                }
+                Ok(())
+            })?;
+        }
+
+        if user_version < 9 {
+            eprintln!("Updating database to version 9...");
+            migrate(&mut connection, 9, |connection| {
+                // the defaults are what every clip was encoded with up to now
+                connection.execute_batch(
+                    "
+                    ALTER TABLE clips ADD COLUMN bitrate INTEGER NOT NULL DEFAULT 24000;
+                    ALTER TABLE clips ADD COLUMN frame_ms REAL NOT NULL DEFAULT 20;
+                    ALTER TABLE clips ADD COLUMN complexity INTEGER NOT NULL DEFAULT 9;
+                    ALTER TABLE clips ADD COLUMN bitrate_mode TEXT NOT NULL DEFAULT 'vbr';
+                    ALTER TABLE clips ADD COLUMN application TEXT NOT NULL DEFAULT 'audio';
+                    ",
+                )?;
+                Ok(())
+            })?;
+        }
+
+        if user_version < 10 {
+            eprintln!("Updating database to version 10...");
+            migrate(&mut connection, 10, |connection| {
+                // everything up to now was recorded in mono
+                connection.execute(
+                    "ALTER TABLE clips ADD COLUMN channels INTEGER NOT NULL DEFAULT 1",
+                    [],
+                )?;
+                Ok(())
+            })?;
+        }
+
+        if user_version < 11 {
+            eprintln!("Updating database to version 11...");
+            migrate(&mut connection, 11, |connection| {
+                connection.execute(
+                    "
+                    CREATE TABLE IF NOT EXISTS markers
+                    (
+                        clip_id INTEGER NOT NULL REFERENCES clips(id) ON DELETE CASCADE,
+                        marker INTEGER NOT NULL,
+                        position_ms INTEGER NOT NULL,
+                        PRIMARY KEY (clip_id, marker)
+                    );
+                    ",
+                    [],
+                )?;
+                Ok(())
+            })?;
         }
 
         Ok(Db(connection))
     }
 
+    // Saves the clip with the default `EncoderSettings`
     pub fn save(&self, clip: &mut AudioClip) -> Result<()> {
-        let (sr, samples) = encode_v1(clip)?;
-        self.0.execute(
-            "
-            INSERT OR REPLACE INTO clips (id, name, date, sample_rate, opus)
-            VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![clip.id, clip.name, clip.date.to_string(), sr, samples],
-        )?;
+        self.save_with(clip, &EncoderSettings::default())
+    }
+
+    pub fn save_with(&self, clip: &mut AudioClip, settings: &EncoderSettings) -> Result<()> {
+        let (_, encoded) = encode_v2(clip, settings)?;
+        let id = self.insert_encoded(clip.id, &clip.name, clip.date, &encoded, settings)?;
 
         // deal with clip id
         if clip.id.is_none() {
-            clip.id = Some(self.0.last_insert_rowid().try_into()?);
+            clip.id = Some(id);
+        }
+
+        Ok(())
+    }
+
+    /// Saves the clip along with the notes and tags of `metadata`, all of them or none
+    pub fn save_with_metadata(
+        &self,
+        clip: &mut AudioClip,
+        settings: &EncoderSettings,
+        metadata: &Metadata,
+    ) -> Result<()> {
+        let (_, encoded) = encode_v2(clip, settings)?;
+
+        // a bad tag rolls the clip back with it when `tx` is dropped
+        let tx = self.0.unchecked_transaction()?;
+        let id = self.insert_encoded(clip.id, &clip.name, clip.date, &encoded, settings)?;
+        if let Some(notes) = &metadata.notes {
+            self.set_notes(&clip.name, Some(notes))?;
+        }
+        for tag in &metadata.tags {
+            self.add_tag(&clip.name, tag)?;
+        }
+        tx.commit()?;
+
+        if clip.id.is_none() {
+            clip.id = Some(id);
         }
 
         Ok(())
     }
 
+    /// Saves a clip that is already v2 encoded with `settings`, as it is.
+    /// Returns the id of the new clip
+    pub fn save_encoded(
+        &self,
+        name: &str,
+        date: DateTime<Utc>,
+        encoded: &[u8],
+        settings: &EncoderSettings,
+    ) -> Result<usize> {
+        self.insert_encoded(None, name, date, encoded, settings)
+    }
+
+    // Writes a v2 encoded clip, over the one with `id` if there is one
+    fn insert_encoded(
+        &self,
+        id: Option<usize>,
+        name: &str,
+        date: DateTime<Utc>,
+        encoded: &[u8],
+        settings: &EncoderSettings,
+    ) -> Result<usize> {
+        let header = HeaderV2::read(encoded)?;
+        // an upsert rather than `INSERT OR REPLACE`, replacing would delete the row
+        // and take its tags with it
+        self.0.execute(
+            "
+            INSERT INTO clips
+            (
+                id, name, date, sample_rate, opus, sample_count, byte_size, codec_version,
+                bitrate, frame_ms, complexity, bitrate_mode, application, channels
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+            ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name,
+                date = excluded.date,
+                sample_rate = excluded.sample_rate,
+                opus = excluded.opus,
+                sample_count = excluded.sample_count,
+                byte_size = excluded.byte_size,
+                codec_version = excluded.codec_version,
+                bitrate = excluded.bitrate,
+                frame_ms = excluded.frame_ms,
+                complexity = excluded.complexity,
+                bitrate_mode = excluded.bitrate_mode,
+                application = excluded.application,
+                channels = excluded.channels",
+            params![
+                id,
+                name,
+                date.to_string(),
+                header.sample_rate,
+                encoded,
+                header.sample_count,
+                encoded.len(),
+                CODEC_VERSION,
+                settings.bitrate,
+                settings.frame_ms,
+                settings.complexity,
+                settings.bitrate_mode.as_str(),
+                application_name(settings.application),
+                header.channels
+            ],
+        )?;
+
+        match id {
+            Some(id) => Ok(id),
+            None => Ok(self.0.last_insert_rowid().try_into()?),
+        }
+    }
+
     pub fn load(&self, name: &str) -> Result<Option<AudioClip>> {
         let mut stmt = self.0.prepare(
             "
-            SELECT id, name, date, sample_rate, opus
+            SELECT id, name, date, sample_rate, opus, codec_version, channels
             FROM clips
-            WHERE name = ?1
+            WHERE name = ?1 AND deleted_at IS NULL
             ",
         )?;
 
         let mut clip_iter = stmt.query_map([name], |row| {
             let _date: String = row.get(2)?; // we need to convert this into a `DateTime` type
             let bytes: Vec<u8> = row.get(4)?;
-            let sample_rate: u32 = row.get(3)?;
-            let samples = decode_v1(sample_rate, &bytes).map_err(|_| {
-                rusqlite::Error::InvalidColumnType(3, "opus".to_string(), Type::Blob)
-            })?;
+            let codec_version: u32 = row.get(5)?;
 
-            Ok(AudioClip {
+            // the samples are decoded once we are out of here, so a corrupt clip
+            // gets reported as that and not as a database error
+            let clip = AudioClip {
                 id: Some(row.get(0)?),
                 name: row.get(1)?,
                 date: _date.parse().map_err(|_| {
                     rusqlite::Error::InvalidColumnType(2, "date".to_string(), Type::Text)
                 })?,
                 sample_rate: row.get(3)?,
-                samples,
-            })
+                channels: row.get(6)?,
+                samples: Vec::new(),
+            };
+
+            Ok((clip, codec_version, bytes))
         })?;
 
         // Basically we will check if our iterator is empty or no
         // i.e. if it has a audio clip or not, if it is there return it
         // else return None
-        Ok(if let Some(clip) = clip_iter.next() {
-            Some(clip?)
+        Ok(if let Some(row) = clip_iter.next() {
+            let (mut clip, codec_version, bytes) = row?;
+            clip.samples = decode(codec_version, clip.sample_rate, &bytes)?;
+            Some(clip)
         } else {
             None
         })
@@ -176,20 +719,22 @@ impl Db {
 
     // get the id of the last recorded clip since we are using
     // an auto increment id, we can just get the max id
-    fn get_last_id(&self) -> Result<u32, rusqlite::Error> {
-        let id = self
-            .0
-            .query_row("SELECT MAX(id) FROM clips", [], |row| row.get(0));
-
-        id
+    fn get_last_id(&self) -> Result<Option<u32>, rusqlite::Error> {
+        self.0.query_row(
+            "SELECT MAX(id) FROM clips WHERE deleted_at IS NULL",
+            [],
+            |row| row.get(0),
+        )
     }
 
     // Load the last clip
     pub fn load_last(&self) -> Result<Option<AudioClip>> {
-        let last_clip_id = self.get_last_id()?;
+        let Some(last_clip_id) = self.get_last_id()? else {
+            return Ok(None);
+        };
 
         let mut stmt = self.0.prepare(
-            "SELECT id, name, date, sample_rate, opus
+            "SELECT id, name, date, sample_rate, opus, codec_version, channels
             FROM clips
             WHERE id = ?1
             ",
@@ -199,51 +744,387 @@ impl Db {
             let _date: String = row.get(2)?; // we need to convert this into a `DateTime` type
             let sample_rate: u32 = row.get(3)?;
             let bytes: Vec<u8> = row.get(4)?;
-            let samples = decode_v1(sample_rate, &bytes).map_err(|_| {
-                rusqlite::Error::InvalidColumnType(3, "opus".to_string(), Type::Blob)
-            })?;
+            let codec_version: u32 = row.get(5)?;
 
-            Ok(AudioClip::new(
+            let clip = AudioClip::new(
                 sample_rate,
-                samples,
+                row.get(6)?,
+                Vec::new(),
                 Some(last_clip_id as usize),
                 row.get(1)?,
                 _date.parse().map_err(|_| {
                     rusqlite::Error::InvalidColumnType(2, "date".to_string(), Type::Text)
                 })?,
-            ))
+            );
+
+            Ok((clip, codec_version, bytes))
         })?;
 
         // Basically we will check if our iterator is empty or no
         // i.e. if it has a audio clip or not, if it is there return it
         // else return None
-        Ok(if let Some(clip) = clip_iter.next() {
-            Some(clip?)
+        Ok(if let Some(row) = clip_iter.next() {
+            let (mut clip, codec_version, bytes) = row?;
+            clip.samples = decode(codec_version, clip.sample_rate, &bytes)?;
+            Some(clip)
         } else {
             None
         })
     }
 
     // so this would retrive the information of the clips, just the basic Info
-    // like name, id and date. Right now this does not take any args
-    // but maybe i will add something like a filter later.
-    pub fn list(&self) -> Result<Vec<ClipMeta>> {
+    // like name, id, date and tags.
+    // If `tags` is not empty only the clips that have *all* of the given tags are returned
+    pub fn list(&self, tags: &[String]) -> Result<Vec<ClipMeta>> {
+        let mut tags = tags
+            .iter()
+            .map(|tag| validate_tag(tag))
+            .collect::<Result<Vec<_>>>()?;
+        tags.sort();
+        tags.dedup();
+
+        // `?1` is the number of tags a clip has to match, the rest are the tags themselves
+        let placeholders = (2..tags.len() + 2)
+            .map(|i| format!("?{}", i))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut stmt = self.0.prepare(&format!(
+            "
+            SELECT {}
+            FROM clips c
+            WHERE c.deleted_at IS NULL AND (?1 = 0 OR ?1 = (
+                SELECT COUNT(*)
+                FROM clip_tags ct JOIN tags t ON t.id = ct.tag_id
+                WHERE ct.clip_id = c.id AND t.name IN ({})
+            ))
+            ORDER BY c.date
+            ",
+            CLIP_META_COLUMNS, placeholders
+        ))?;
+
+        let params = std::iter::once(Value::Integer(tags.len().try_into()?))
+            .chain(tags.iter().map(|tag| Value::Text(tag.to_string())))
+            .collect::<Vec<_>>();
+
+        let clip_iter = stmt.query_map(params_from_iter(params), clip_meta_from_row)?;
+
+        Ok(clip_iter.collect::<Result<_, rusqlite::Error>>()?)
+    }
+
+    // Full text search over the names, notes and tags of the clips, best matches first.
+    // Every word in `query` has to be there, `"exact phrase"` and `prefix*` work as well.
+    // Anything else is searched for as it is, see `fts_query`
+    pub fn search(&self, query: &str) -> Result<Vec<SearchResult>> {
+        let mut stmt = self.0.prepare(&format!(
+            "
+            SELECT {},
+                snippet(clips_fts, -1, '[', ']', '...', 8),
+                bm25(clips_fts)
+            FROM clips_fts JOIN clips c ON c.id = clips_fts.rowid
+            WHERE clips_fts MATCH ?1 AND c.deleted_at IS NULL
+            ORDER BY bm25(clips_fts)
+            ",
+            CLIP_META_COLUMNS
+        ))?;
+
+        // whatever FTS5 still can't take is the query's fault and not the database's
+        let invalid = |err| match err {
+            rusqlite::Error::SqliteFailure(_, Some(message)) if message.starts_with("fts5:") => {
+                Error::InvalidSearch(format!("`{}`", query), message)
+            }
+            err => err.into(),
+        };
+
+        let result_iter = stmt
+            .query_map([fts_query(query)], |row| {
+                Ok(SearchResult {
+                    meta: clip_meta_from_row(row)?,
+                    snippet: row.get(CLIP_META_COLUMN_COUNT)?,
+                    rank: row.get(CLIP_META_COLUMN_COUNT + 1)?,
+                })
+            })
+            .map_err(invalid)?;
+
+        result_iter
+            .collect::<Result<_, rusqlite::Error>>()
+            .map_err(invalid)
+    }
+
+    // looks up the id of a clip by name, erroring out if there is no such clip
+    // (clips in the trash don't count)
+    fn clip_id(&self, name: &str) -> Result<i64> {
+        let mut stmt = self
+            .0
+            .prepare("SELECT id FROM clips WHERE name = ?1 AND deleted_at IS NULL")?;
+        let mut ids = stmt.query_map([name], |row| row.get(0))?;
+
+        match ids.next() {
+            Some(id) => Ok(id?),
+            None => Err(Error::ClipNotFound(name.to_string())),
+        }
+    }
+
+    // The metadata of a single clip, without decoding its audio
+    pub fn info(&self, name: &str) -> Result<ClipMeta> {
+        let clip_id = self.clip_id(name)?;
+
+        Ok(self.0.query_row(
+            &format!("SELECT {} FROM clips c WHERE c.id = ?1", CLIP_META_COLUMNS),
+            [clip_id],
+            clip_meta_from_row,
+        )?)
+    }
+
+    // The metadata of a single clip along with its audio as it is stored, still encoded
+    pub fn encoded(&self, name: &str) -> Result<(ClipMeta, Vec<u8>)> {
+        let clip_id = self.clip_id(name)?;
+
+        Ok(self.0.query_row(
+            &format!(
+                "SELECT {}, c.opus FROM clips c WHERE c.id = ?1",
+                CLIP_META_COLUMNS
+            ),
+            [clip_id],
+            |row| Ok((clip_meta_from_row(row)?, row.get(CLIP_META_COLUMN_COUNT)?)),
+        )?)
+    }
+
+    // Get the notes written for the clip, if there are any
+    pub fn notes(&self, name: &str) -> Result<Option<String>> {
+        let clip_id = self.clip_id(name)?;
+
+        Ok(self
+            .0
+            .query_row("SELECT notes FROM clips WHERE id = ?1", [clip_id], |row| {
+                row.get(0)
+            })?)
+    }
+
+    // Replace the notes of the clip, blank notes are stored as no notes at all
+    pub fn set_notes(&self, name: &str, notes: Option<&str>) -> Result<()> {
+        let clip_id = self.clip_id(name)?;
+        let notes = notes.map(str::trim).filter(|notes| !notes.is_empty());
+
+        self.0.execute(
+            "UPDATE clips SET notes = ?1 WHERE id = ?2",
+            params![notes, clip_id],
+        )?;
+
+        Ok(())
+    }
+
+    // Stores the transcript of the clip, replacing the one it had before
+    pub fn save_transcript(&self, name: &str, transcript: &Transcript) -> Result<()> {
+        let clip_id = self.clip_id(name)?;
+        let tx = self.0.unchecked_transaction()?;
+
+        tx.execute("DELETE FROM transcripts WHERE clip_id = ?1", [clip_id])?;
+
+        for (i, segment) in transcript.segments.iter().enumerate() {
+            tx.execute(
+                "
+                INSERT INTO transcripts (clip_id, segment, start_ms, end_ms, text)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    clip_id,
+                    i,
+                    segment.start_ms,
+                    segment.end_ms,
+                    segment.text
+                ],
+            )?;
+        }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    // Load the transcript of the clip, `None` if it was never transcribed
+    pub fn transcript(&self, name: &str) -> Result<Option<Transcript>> {
+        let clip_id = self.clip_id(name)?;
+
         let mut stmt = self.0.prepare(
             "
-            SELECT id, name, date
-            FROM clips
-            ORDER BY date
+            SELECT start_ms, end_ms, text
+            FROM transcripts
+            WHERE clip_id = ?1
+            ORDER BY segment
             ",
         )?;
 
+        let segment_iter = stmt.query_map([clip_id], |row| {
+            Ok(Segment {
+                start_ms: row.get(0)?,
+                end_ms: row.get(1)?,
+                text: row.get(2)?,
+            })
+        })?;
+
+        let segments: Vec<_> = segment_iter.collect::<Result<_, rusqlite::Error>>()?;
+
+        Ok(if segments.is_empty() {
+            None
+        } else {
+            Some(Transcript { segments })
+        })
+    }
+
+    // Stores the markers of the clip, replacing the ones it had before
+    pub fn save_markers(&self, name: &str, markers: &[Marker]) -> Result<()> {
+        let clip_id = self.clip_id(name)?;
+        let tx = self.0.unchecked_transaction()?;
+
+        tx.execute("DELETE FROM markers WHERE clip_id = ?1", [clip_id])?;
+
+        for (i, marker) in markers.iter().enumerate() {
+            tx.execute(
+                "
+                INSERT INTO markers (clip_id, marker, position_ms)
+                VALUES (?1, ?2, ?3)",
+                params![clip_id, i, marker.position_ms],
+            )?;
+        }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    // The markers of the clip in the order they were made, empty if it has none
+    pub fn markers(&self, name: &str) -> Result<Vec<Marker>> {
+        let clip_id = self.clip_id(name)?;
+
+        let mut stmt = self.0.prepare(
+            "
+            SELECT position_ms
+            FROM markers
+            WHERE clip_id = ?1
+            ORDER BY marker
+            ",
+        )?;
+
+        let marker_iter = stmt.query_map([clip_id], |row| {
+            Ok(Marker {
+                position_ms: row.get(0)?,
+            })
+        })?;
+
+        Ok(marker_iter.collect::<Result<_, rusqlite::Error>>()?)
+    }
+
+    // Tags the clip, tagging it again with the same tag does nothing
+    pub fn add_tag(&self, name: &str, tag: &str) -> Result<()> {
+        let tag = validate_tag(tag)?;
+        let clip_id = self.clip_id(name)?;
+
+        self.0
+            .execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", [tag])?;
+        self.0.execute(
+            "
+            INSERT OR IGNORE INTO clip_tags (clip_id, tag_id)
+            SELECT ?1, id FROM tags WHERE name = ?2
+            ",
+            params![clip_id, tag],
+        )?;
+
+        Ok(())
+    }
+
+    // Removes the tag from the clip, and forgets the tag entirely if nothing uses it anymore
+    pub fn remove_tag(&self, name: &str, tag: &str) -> Result<()> {
+        let tag = validate_tag(tag)?;
+        let clip_id = self.clip_id(name)?;
+
+        self.0.execute(
+            "
+            DELETE FROM clip_tags
+            WHERE clip_id = ?1 AND tag_id = (SELECT id FROM tags WHERE name = ?2)
+            ",
+            params![clip_id, tag],
+        )?;
+        self.0.execute(
+            "DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM clip_tags)",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    /// Whether a clip has this name, counting the ones in the trash
+    pub fn name_taken(&self, name: &str) -> Result<bool> {
+        Ok(self.0.query_row(
+            "SELECT EXISTS (SELECT 1 FROM clips WHERE name = ?1)",
+            [name],
+            |row| row.get(0),
+        )?)
+    }
+
+    // Renames the clip, its id (and everything attached to it) stays the same.
+    // Fails if the new name is already used, even by a clip in the trash
+    pub fn rename(&self, old: &str, new: &str) -> Result<()> {
+        let clip_id = self.clip_id(old)?;
+
+        // the clip itself doesn't count as taking the name
+        if old == new {
+            return Ok(());
+        }
+
+        if self.name_taken(new)? {
+            return Err(Error::NameTaken(new.to_string()));
+        }
+
+        self.0.execute(
+            "UPDATE clips SET name = ?1 WHERE id = ?2",
+            params![new, clip_id],
+        )?;
+
+        Ok(())
+    }
+
+    // Moves the clip to the trash, it is gone from everything else
+    // but can be restored until the trash is emptied
+    pub fn delete(&self, name: &str) -> Result<()> {
+        let deleted = self.0.execute(
+            "
+            UPDATE clips
+            SET deleted_at = ?2
+            WHERE name = ?1 AND deleted_at IS NULL
+            ",
+            params![name, Utc::now().timestamp()],
+        )?;
+
+        if deleted == 0 {
+            return Err(Error::ClipNotFound(name.to_string()));
+        }
+
+        Ok(())
+    }
+
+    // The clips in the trash, most recently deleted first
+    pub fn trash(&self) -> Result<Vec<TrashedClip>> {
+        let mut stmt = self.0.prepare(&format!(
+            "
+            SELECT {}, c.deleted_at
+            FROM clips c
+            WHERE c.deleted_at IS NOT NULL
+            ORDER BY c.deleted_at DESC
+            ",
+            CLIP_META_COLUMNS
+        ))?;
+
         let clip_iter = stmt.query_map([], |row| {
-            let _date: String = row.get(2)?; // we need to convert this into a `DateTime` type
+            let deleted_at: i64 = row.get(CLIP_META_COLUMN_COUNT)?;
 
-            Ok(ClipMeta {
-                clip_id: row.get(0)?,
-                clip_name: row.get(1)?,
-                clip_date: _date.parse().map_err(|_| {
-                    rusqlite::Error::InvalidColumnType(2, "date".to_string(), Type::Text)
+            Ok(TrashedClip {
+                meta: clip_meta_from_row(row)?,
+                deleted_at: DateTime::from_timestamp(deleted_at, 0).ok_or_else(|| {
+                    rusqlite::Error::InvalidColumnType(
+                        CLIP_META_COLUMN_COUNT,
+                        "deleted_at".to_string(),
+                        Type::Integer,
+                    )
                 })?,
             })
         })?;
@@ -251,15 +1132,52 @@ impl Db {
         Ok(clip_iter.collect::<Result<_, rusqlite::Error>>()?)
     }
 
-    pub fn delete(&self, name: &str) -> Result<()> {
-        self.0.execute(
+    // Is there a clip with this name in the trash
+    pub fn in_trash(&self, name: &str) -> Result<bool> {
+        Ok(self.0.query_row(
+            "SELECT EXISTS (SELECT 1 FROM clips WHERE name = ?1 AND deleted_at IS NOT NULL)",
+            [name],
+            |row| row.get(0),
+        )?)
+    }
+
+    // Takes the clip back out of the trash
+    pub fn restore(&self, name: &str) -> Result<()> {
+        let restored = self.0.execute(
             "
-            DELETE FROM clips
-            WHERE name = ?1
+            UPDATE clips
+            SET deleted_at = NULL
+            WHERE name = ?1 AND deleted_at IS NOT NULL
             ",
-            params![name],
+            [name],
         )?;
 
+        if restored == 0 {
+            return Err(Error::NotInTrash(name.to_string()));
+        }
+
         Ok(())
     }
+
+    // Deletes everything in the trash for good, returns how many clips were deleted
+    pub fn empty_trash(&self) -> Result<usize> {
+        Ok(self
+            .0
+            .execute("DELETE FROM clips WHERE deleted_at IS NOT NULL", [])?)
+    }
+
+    // Deletes the clips that have been in the trash for longer than `retention`
+    // for good, returns how many clips were deleted
+    pub fn purge_trash(&self, retention: chrono::Duration) -> Result<usize> {
+        // a retention reaching back further than dates go keeps everything
+        let Some(cutoff) = Utc::now().checked_sub_signed(retention) else {
+            return Ok(0);
+        };
+        let cutoff = cutoff.timestamp();
+
+        Ok(self.0.execute(
+            "DELETE FROM clips WHERE deleted_at IS NOT NULL AND deleted_at < ?1",
+            [cutoff],
+        )?)
+    }
 }
@@ -0,0 +1,237 @@
+#![allow(non_snake_case)]
+//! Finding the audio devices clips are recorded from and played on.
+//!
+//! Devices are picked by name or by their index in `Oxygen devices`,
+//! on the default host unless another one is asked for.
+
+use crate::error::{Error, Result};
+use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::{Device, Host, SampleFormat, SampleRate, SupportedStreamConfig};
+use serde::{Deserialize, Serialize};
+
+// The sample formats `record` and `play` know how to handle
+const SAMPLE_FORMATS: [SampleFormat; 3] = [SampleFormat::F32, SampleFormat::I16, SampleFormat::U16];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Input,
+    Output,
+}
+
+/// Which device to use and at what rate, anything left out is the default of the host
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeviceChoice {
+    /// Name of the audio host, like `ALSA` or `JACK`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// Name of the device, or its index in the list of devices of the host
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    /// Sample rate to open the device at, in Hz
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate: Option<u32>,
+}
+
+impl DeviceChoice {
+    /// Whatever `self` says, and `fallback` for anything it leaves out
+    pub fn or(self, fallback: DeviceChoice) -> DeviceChoice {
+        DeviceChoice {
+            host: self.host.or(fallback.host),
+            device: self.device.or(fallback.device),
+            rate: self.rate.or(fallback.rate),
+        }
+    }
+}
+
+/// A range of configurations a device can be opened with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigRange {
+    pub channels: u16,
+    pub min_rate: u32,
+    pub max_rate: u32,
+    pub sample_format: String,
+}
+
+/// A device as `Oxygen devices` lists it
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    /// What `--device` takes instead of the name
+    pub index: usize,
+    pub name: String,
+    pub default: bool,
+    pub configs: Vec<ConfigRange>,
+}
+
+/// An audio host and the devices on it
+#[derive(Debug, Clone)]
+pub struct HostInfo {
+    pub name: String,
+    pub default: bool,
+    pub inputs: Vec<DeviceInfo>,
+    pub outputs: Vec<DeviceInfo>,
+}
+
+/// Every host that can be opened on this machine, with its devices.
+/// Hosts and devices that fail to answer are left out
+pub fn list() -> Vec<HostInfo> {
+    let default_host = cpal::default_host().id();
+
+    cpal::available_hosts()
+        .into_iter()
+        .filter_map(|id| cpal::host_from_id(id).ok())
+        .map(|host| HostInfo {
+            name: host.id().name().to_string(),
+            default: host.id() == default_host,
+            inputs: device_infos(&host, Direction::Input),
+            outputs: device_infos(&host, Direction::Output),
+        })
+        .collect()
+}
+
+fn device_infos(host: &Host, direction: Direction) -> Vec<DeviceInfo> {
+    let default_name = match direction {
+        Direction::Input => host.default_input_device(),
+        Direction::Output => host.default_output_device(),
+    }
+    .and_then(|device| device.name().ok());
+
+    devices(host, direction)
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(index, device)| {
+            let name = device.name().unwrap_or_else(|_| "(no name)".to_string());
+            let configs = match direction {
+                Direction::Input => device.supported_input_configs().map(|c| c.collect()),
+                Direction::Output => device.supported_output_configs().map(|c| c.collect()),
+            }
+            .unwrap_or_else(|_| Vec::new());
+
+            let mut configs: Vec<ConfigRange> = configs
+                .into_iter()
+                .map(|config: cpal::SupportedStreamConfigRange| ConfigRange {
+                    channels: config.channels(),
+                    min_rate: config.min_sample_rate().0,
+                    max_rate: config.max_sample_rate().0,
+                    sample_format: config.sample_format().to_string(),
+                })
+                .collect();
+            // hosts like ALSA list the same range once for every buffer size
+            configs.dedup();
+
+            DeviceInfo {
+                index,
+                default: default_name.as_ref() == Some(&name),
+                name,
+                configs,
+            }
+        })
+        .collect()
+}
+
+fn host(name: Option<&str>) -> Result<Host> {
+    let Some(name) = name else {
+        return Ok(cpal::default_host());
+    };
+
+    let id = cpal::available_hosts()
+        .into_iter()
+        .find(|id| id.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| Error::NoSuchHost(name.to_string()))?;
+
+    Ok(cpal::host_from_id(id)?)
+}
+
+fn devices(host: &Host, direction: Direction) -> Result<Vec<Device>> {
+    Ok(match direction {
+        Direction::Input => host.input_devices()?.collect(),
+        Direction::Output => host.output_devices()?.collect(),
+    })
+}
+
+/// The device `choice` asks for.
+///
+/// A number is the index of the device, anything else its name,
+/// or a part of the name no other device shares (ignoring case)
+pub fn device(direction: Direction, choice: &DeviceChoice) -> Result<Device> {
+    let host = host(choice.host.as_deref())?;
+
+    let Some(wanted) = choice.device.as_deref() else {
+        return match direction {
+            Direction::Input => host.default_input_device().ok_or(Error::NoInputDevice),
+            Direction::Output => host.default_output_device().ok_or(Error::NoOutputDevice),
+        };
+    };
+
+    let mut devices = devices(&host, direction)?;
+    let not_found = || Error::NoSuchDevice(wanted.to_string());
+
+    if let Ok(index) = wanted.parse::<usize>() {
+        return (index < devices.len())
+            .then(|| devices.swap_remove(index))
+            .ok_or_else(not_found);
+    }
+
+    let names: Vec<String> = devices
+        .iter()
+        .map(|device| device.name().unwrap_or_default())
+        .collect();
+
+    let position = names.iter().position(|name| name == wanted).or_else(|| {
+        let wanted = wanted.to_lowercase();
+        let mut matching = names
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| name.to_lowercase().contains(&wanted))
+            .map(|(i, _)| i);
+
+        match (matching.next(), matching.next()) {
+            (Some(i), None) => Some(i),
+            _ => None,
+        }
+    });
+
+    position
+        .map(|i| devices.swap_remove(i))
+        .ok_or_else(not_found)
+}
+
+/// The configuration to open `device` with, its default one at `rate` if there is one.
+///
+/// Of the configurations that can do the rate, the one closest to the default wins
+pub fn config(
+    device: &Device,
+    direction: Direction,
+    rate: Option<u32>,
+) -> Result<SupportedStreamConfig> {
+    let default = match direction {
+        Direction::Input => device.default_input_config()?,
+        Direction::Output => device.default_output_config()?,
+    };
+
+    let Some(rate) = rate else {
+        return Ok(default);
+    };
+    if default.sample_rate().0 == rate {
+        return Ok(default);
+    }
+
+    let configs: Vec<_> = match direction {
+        Direction::Input => device.supported_input_configs()?.collect(),
+        Direction::Output => device.supported_output_configs()?.collect(),
+    };
+
+    configs
+        .into_iter()
+        .filter(|config| SAMPLE_FORMATS.contains(&config.sample_format()))
+        .filter_map(|config| config.try_with_sample_rate(SampleRate(rate)))
+        .max_by_key(|config| {
+            (
+                config.channels() == default.channels(),
+                config.sample_format() == default.sample_format(),
+                config.sample_format() == SampleFormat::F32,
+            )
+        })
+        .ok_or(Error::UnsupportedSampleRate(rate))
+}
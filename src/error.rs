@@ -0,0 +1,135 @@
+#![allow(non_snake_case)]
+use thiserror::Error;
+
+/// Every error the journal API can return.
+///
+/// The binary just bubbles these up through `color_eyre`, but library users
+/// can match on the variant to decide what to do.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("No input device found")]
+    NoInputDevice,
+
+    #[error("No output device found")]
+    NoOutputDevice,
+
+    #[error("Unsupported Sample Format")]
+    UnsupportedSampleFormat,
+
+    #[error("No clip with the name {0} found")]
+    ClipNotFound(String),
+
+    #[error("A clip with the name {0} already exists")]
+    NameTaken(String),
+
+    #[error("No clip with the name {0} in the trash")]
+    NotInTrash(String),
+
+    /// Tags can't be empty or contain a `,`
+    #[error("Invalid tag `{0}`")]
+    InvalidTag(String),
+
+    /// Clips are stored as opus, which only does mono and stereo
+    #[error("Unsupported number of channels {0}, only mono and stereo clips can be stored")]
+    UnsupportedChannels(u16),
+
+    #[error("Channel {0} does not exist, the clip has {1} channel(s)")]
+    NoSuchChannel(usize, u16),
+
+    /// `--host` or the config file named a host cpal doesn't know
+    #[error("No audio host called {0}, `Oxygen devices` lists them")]
+    NoSuchHost(String),
+
+    #[error("No audio device called {0}, `Oxygen devices` lists them")]
+    NoSuchDevice(String),
+
+    #[error("The device can't be opened at {0} Hz")]
+    UnsupportedSampleRate(u32),
+
+    /// The config file could not be read or written, the first field is its path
+    #[error("Invalid config file {0}: {1}")]
+    Config(String, String),
+
+    #[error("Invalid encoder settings: {0}")]
+    InvalidEncoderSettings(String),
+
+    #[error("Invalid recording limits: {0}")]
+    InvalidRecordLimits(String),
+
+    #[error("Invalid export settings: {0}")]
+    InvalidExportSettings(String),
+
+    /// A search the full text index can't make sense of, with what it said about it
+    #[error("Invalid search {0}: {1}")]
+    InvalidSearch(String, String),
+
+    /// The path handed to `export` can't be used
+    #[error("Invalid path: {0}")]
+    InvalidPath(String),
+
+    /// The file handed to `import` is missing something we need
+    #[error("Could not import file: {0}")]
+    Import(String),
+
+    /// What `export` was asked to write doesn't add up, like a range past the end of the clip
+    #[error("Could not export: {0}")]
+    Export(String),
+
+    /// A stored blob could not be decoded, most likely it is corrupt
+    #[error("Could not decode clip: {0}")]
+    Decode(String),
+
+    /// A spool file of an interrupted recording can't be read
+    #[error("Could not recover recording: {0}")]
+    Spool(String),
+
+    #[error("Could not transcribe clip: {0}")]
+    Transcription(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error(transparent)]
+    Opus(#[from] audiopus::Error),
+
+    #[error(transparent)]
+    Symphonia(#[from] symphonia::core::errors::Error),
+
+    #[error(transparent)]
+    Wav(#[from] hound::Error),
+
+    #[error(transparent)]
+    DeviceName(#[from] cpal::DeviceNameError),
+
+    #[error(transparent)]
+    StreamConfig(#[from] cpal::DefaultStreamConfigError),
+
+    #[error(transparent)]
+    HostUnavailable(#[from] cpal::HostUnavailable),
+
+    #[error(transparent)]
+    Devices(#[from] cpal::DevicesError),
+
+    #[error(transparent)]
+    SupportedStreamConfigs(#[from] cpal::SupportedStreamConfigsError),
+
+    #[error(transparent)]
+    BuildStream(#[from] cpal::BuildStreamError),
+
+    #[error(transparent)]
+    PlayStream(#[from] cpal::PlayStreamError),
+
+    #[error(transparent)]
+    Signal(#[from] ctrlc::Error),
+
+    #[error(transparent)]
+    Channel(#[from] std::sync::mpsc::RecvError),
+
+    #[error(transparent)]
+    IntConversion(#[from] std::num::TryFromIntError),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,780 @@
+#![allow(non_snake_case)]
+//! A small FLAC encoder, for exporting clips losslessly.
+//!
+//! Every block is tried with the fixed predictors and (from level 3 on) LPC,
+//! stereo clips also with left/side, right/side and mid/side, and whatever is
+//! smallest gets written. Residuals are rice coded in partitions.
+//! See RFC 9639 for the format.
+
+use crate::dither;
+use crate::error::{Error, Result};
+use crate::metadata::Metadata;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// How a clip gets written as FLAC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlacSettings {
+    // 8, 16 or 24
+    pub bits_per_sample: u8,
+    // 0 to 8, higher is slower and smaller, like the `flac` tool
+    pub compression_level: u8,
+    // TPDF dither when going down to 8, 16 or 24 bits
+    pub dither: bool,
+}
+
+impl Default for FlacSettings {
+    fn default() -> Self {
+        Self {
+            bits_per_sample: 16,
+            compression_level: 5,
+            dither: true,
+        }
+    }
+}
+
+impl FlacSettings {
+    /// Checks the settings are something the encoder can do
+    pub fn validate(&self) -> Result<()> {
+        if ![8, 16, 24].contains(&self.bits_per_sample) {
+            return Err(Error::InvalidExportSettings(format!(
+                "FLAC bit depth has to be 8, 16 or 24, got {}",
+                self.bits_per_sample
+            )));
+        }
+
+        if self.compression_level > 8 {
+            return Err(Error::InvalidExportSettings(format!(
+                "FLAC compression level has to be between 0 and 8, got {}",
+                self.compression_level
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+// What a compression level turns into
+struct Level {
+    block_size: usize,
+    max_fixed_order: usize,
+    max_lpc_order: usize,
+    // try every LPC order instead of guessing the best one
+    exhaustive: bool,
+    max_partition_order: u32,
+    // try left/side, right/side and mid/side on stereo clips
+    decorrelate: bool,
+}
+
+impl Level {
+    fn new(level: u8) -> Self {
+        let (block_size, max_fixed_order, max_lpc_order, max_partition_order, decorrelate) =
+            match level {
+                0 => (1152, 2, 0, 3, false),
+                1 => (1152, 2, 0, 3, true),
+                2 => (1152, 4, 0, 3, true),
+                3 => (4096, 4, 6, 4, false),
+                4 => (4096, 4, 8, 4, true),
+                5 => (4096, 4, 8, 5, true),
+                6 => (4096, 4, 8, 6, true),
+                _ => (4096, 4, 12, 6, true),
+            };
+
+        Self {
+            block_size,
+            max_fixed_order,
+            max_lpc_order,
+            exhaustive: level == 8,
+            max_partition_order,
+            decorrelate,
+        }
+    }
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            acc: 0,
+            bits: 0,
+        }
+    }
+
+    // the lowest `n` bits of `value`, at most 32
+    fn write(&mut self, value: u64, n: u32) {
+        if n == 0 {
+            return;
+        }
+
+        self.acc = (self.acc << n) | (value & ((1 << n) - 1));
+        self.bits += n;
+
+        while self.bits >= 8 {
+            self.bits -= 8;
+            self.bytes.push((self.acc >> self.bits) as u8);
+        }
+        self.acc &= (1 << self.bits) - 1;
+    }
+
+    fn write_signed(&mut self, value: i64, n: u32) {
+        self.write(value as u64, n);
+    }
+
+    // `zeros` zeros and a one
+    fn write_unary(&mut self, mut zeros: u64) {
+        while zeros >= 32 {
+            self.write(0, 32);
+            zeros -= 32;
+        }
+        self.write(1, zeros as u32 + 1);
+    }
+
+    fn write_rice(&mut self, value: i64, param: u32) {
+        let folded = fold(value);
+        self.write_unary(folded >> param);
+        self.write(folded, param);
+    }
+
+    // pads with zeros up to the next byte
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.write(0, 8 - self.bits);
+        }
+    }
+}
+
+// signed residual to the unsigned number that gets rice coded
+fn fold(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0u16, |mut crc, &byte| {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+// Frame numbers are stored like UTF-8 characters
+fn write_utf8(writer: &mut BitWriter, value: u64) {
+    if value < 0x80 {
+        writer.write(value, 8);
+        return;
+    }
+
+    // `len` bytes hold `5 * len + 1` bits, the first one starts with `len` ones
+    let len = (2..=7).find(|&n| value < 1 << (5 * n + 1)).unwrap_or(7);
+    let prefix = (0xFF00u64 >> len) & 0xFF;
+
+    writer.write(prefix | (value >> (6 * (len - 1))), 8);
+    for i in (0..len - 1).rev() {
+        writer.write(0x80 | ((value >> (6 * i)) & 0x3F), 8);
+    }
+}
+
+// Rice coded residual, split into `1 << order` partitions with a parameter each
+struct Residual {
+    order: u32,
+    params: Vec<u32>,
+    // parameters over 14 need the 5 bit parameter coding
+    wide: bool,
+    bits: u64,
+}
+
+// The best rice parameter for `count` samples adding up to `sum` once folded, and its cost
+fn rice_param(sum: u64, count: u64) -> (u32, u64) {
+    let cost = |k: u32| count * (k as u64 + 1) + (sum >> k);
+
+    if count == 0 {
+        return (0, 0);
+    }
+
+    let guess = (sum / count).max(1).ilog2().min(30);
+    (guess.saturating_sub(1)..=(guess + 1).min(30))
+        .map(|k| (k, cost(k)))
+        .min_by_key(|&(_, bits)| bits)
+        .unwrap_or((0, cost(0)))
+}
+
+// Picks the partition order and rice parameters for the residual of a block
+// predicted with `warmup` samples
+fn plan_residual(residual: &[i64], block_size: usize, warmup: usize, max_order: u32) -> Residual {
+    // the most partitions the block can be split into
+    let mut top = 0;
+    while top < max_order
+        && block_size.is_multiple_of(1 << (top + 1))
+        && (block_size >> (top + 1)) > warmup
+    {
+        top += 1;
+    }
+
+    // sums of every partition at the top order, merged pairwise for the lower ones
+    let partition_len = block_size >> top;
+    let mut sums: Vec<u64> = (0..1usize << top)
+        .map(|p| {
+            let start = (p * partition_len).saturating_sub(warmup);
+            let end = (p + 1) * partition_len - warmup;
+            residual[start..end].iter().map(|&r| fold(r)).sum()
+        })
+        .collect();
+
+    let mut best: Option<Residual> = None;
+    for order in (0..=top).rev() {
+        let partition_len = block_size >> order;
+        let mut params = Vec::with_capacity(sums.len());
+        let mut bits = 0;
+
+        for (p, &sum) in sums.iter().enumerate() {
+            let count = partition_len - if p == 0 { warmup } else { 0 };
+            let (param, cost) = rice_param(sum, count as u64);
+            params.push(param);
+            bits += cost;
+        }
+
+        let wide = params.iter().any(|&p| p > 14);
+        bits += 2 + 4 + params.len() as u64 * if wide { 5 } else { 4 };
+
+        if best.as_ref().is_none_or(|b| bits < b.bits) {
+            best = Some(Residual {
+                order,
+                params,
+                wide,
+                bits,
+            });
+        }
+
+        sums = sums.chunks(2).map(|pair| pair.iter().sum()).collect();
+    }
+
+    best.expect("there is always partition order 0")
+}
+
+fn write_residual(writer: &mut BitWriter, plan: &Residual, residual: &[i64], block_size: usize) {
+    writer.write(plan.wide as u64, 2);
+    writer.write(plan.order as u64, 4);
+
+    let partition_len = block_size >> plan.order;
+    let warmup = block_size - residual.len();
+    let mut start = 0;
+
+    for (p, &param) in plan.params.iter().enumerate() {
+        let end = (p + 1) * partition_len - warmup;
+        writer.write(param as u64, if plan.wide { 5 } else { 4 });
+        for &r in &residual[start..end] {
+            writer.write_rice(r, param);
+        }
+        start = end;
+    }
+}
+
+enum Subframe {
+    Constant(i64),
+    Verbatim,
+    Fixed {
+        order: usize,
+        residual: Vec<i64>,
+        plan: Residual,
+    },
+    Lpc {
+        precision: u32,
+        shift: u32,
+        coefficients: Vec<i64>,
+        residual: Vec<i64>,
+        plan: Residual,
+    },
+}
+
+// Residual of the fixed polynomial predictor of `order`
+fn fixed_residual(samples: &[i64], order: usize) -> Vec<i64> {
+    (order..samples.len())
+        .map(|i| {
+            let s = |back: usize| samples[i - back];
+            match order {
+                0 => s(0),
+                1 => s(0) - s(1),
+                2 => s(0) - 2 * s(1) + s(2),
+                3 => s(0) - 3 * s(1) + 3 * s(2) - s(3),
+                _ => s(0) - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4),
+            }
+        })
+        .collect()
+}
+
+// Autocorrelation of the samples under a welch window, for lags `0..=max_lag`
+fn autocorrelation(samples: &[i64], max_lag: usize) -> Vec<f64> {
+    let n = samples.len() as f64;
+    let half = (n - 1.0) / 2.0;
+    let windowed: Vec<f64> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, &s)| {
+            let x = (i as f64 - half) / (half + 1.0);
+            s as f64 * (1.0 - x * x)
+        })
+        .collect();
+
+    (0..=max_lag)
+        .map(|lag| {
+            windowed[lag..]
+                .iter()
+                .zip(&windowed)
+                .map(|(a, b)| a * b)
+                .sum()
+        })
+        .collect()
+}
+
+// Levinson-Durbin, the predictor coefficients of every order up to `max_order`
+// along with the error each one leaves
+fn lpc_coefficients(autoc: &[f64], max_order: usize) -> Vec<(Vec<f64>, f64)> {
+    let mut orders = Vec::with_capacity(max_order);
+    let mut lpc = vec![0f64; max_order];
+    let mut error = autoc[0];
+
+    for i in 0..max_order {
+        if error <= 0.0 {
+            break;
+        }
+
+        let mut r = -autoc[i + 1];
+        for j in 0..i {
+            r -= lpc[j] * autoc[i - j];
+        }
+        r /= error;
+
+        lpc[i] = r;
+        for j in 0..i / 2 {
+            let tmp = lpc[j];
+            lpc[j] += r * lpc[i - 1 - j];
+            lpc[i - 1 - j] += r * tmp;
+        }
+        if i % 2 == 1 {
+            lpc[i / 2] += lpc[i / 2] * r;
+        }
+
+        error *= 1.0 - r * r;
+
+        // the coefficients predict the next sample, so flip the sign
+        orders.push((lpc[..=i].iter().map(|c| -c).collect(), error));
+    }
+
+    orders
+}
+
+// Quantizes the coefficients to `precision` bits, returning them with their shift
+fn quantize(coefficients: &[f64], precision: u32) -> Option<(Vec<i64>, u32)> {
+    let max = coefficients.iter().fold(0f64, |max, c| max.max(c.abs()));
+    if max <= 0.0 {
+        return None;
+    }
+
+    // as much precision as fits, tiny coefficients just lose some
+    let shift = (precision as i32 - 1 - (max.log2().floor() as i32 + 1)).min(15);
+    if shift < 0 {
+        return None;
+    }
+
+    let limit = 1i64 << (precision - 1);
+    let mut error = 0f64;
+    let quantized = coefficients
+        .iter()
+        .map(|c| {
+            error += c * (1 << shift) as f64;
+            let q = (error.round() as i64).clamp(-limit, limit - 1);
+            error -= q as f64;
+            q
+        })
+        .collect();
+
+    Some((quantized, shift as u32))
+}
+
+fn lpc_residual(samples: &[i64], coefficients: &[i64], shift: u32) -> Option<Vec<i64>> {
+    let order = coefficients.len();
+
+    (order..samples.len())
+        .map(|i| {
+            let prediction: i64 = coefficients
+                .iter()
+                .enumerate()
+                .map(|(j, c)| c * samples[i - 1 - j])
+                .sum();
+            let residual = samples[i] - (prediction >> shift);
+            // residuals have to fit in 32 bits
+            i32::try_from(residual).ok().map(i64::from)
+        })
+        .collect()
+}
+
+fn subframe_bits(subframe: &Subframe, block_size: usize, bps: u32) -> u64 {
+    8 + match subframe {
+        Subframe::Constant(_) => bps as u64,
+        Subframe::Verbatim => block_size as u64 * bps as u64,
+        Subframe::Fixed { order, plan, .. } => *order as u64 * bps as u64 + plan.bits,
+        Subframe::Lpc {
+            precision,
+            coefficients,
+            plan,
+            ..
+        } => coefficients.len() as u64 * (bps + precision) as u64 + 4 + 5 + plan.bits,
+    }
+}
+
+// The smallest way to store one channel of a block
+fn best_subframe(samples: &[i64], bps: u32, level: &Level) -> (Subframe, u64) {
+    let block_size = samples.len();
+
+    if samples.iter().all(|&s| s == samples[0]) {
+        let subframe = Subframe::Constant(samples[0]);
+        let bits = subframe_bits(&subframe, block_size, bps);
+        return (subframe, bits);
+    }
+
+    let mut best = (
+        Subframe::Verbatim,
+        subframe_bits(&Subframe::Verbatim, block_size, bps),
+    );
+    let mut consider = |subframe: Subframe| {
+        let bits = subframe_bits(&subframe, block_size, bps);
+        if bits < best.1 {
+            best = (subframe, bits);
+        }
+    };
+
+    for order in 0..=level.max_fixed_order.min(block_size - 1) {
+        let residual = fixed_residual(samples, order);
+        let plan = plan_residual(&residual, block_size, order, level.max_partition_order);
+        consider(Subframe::Fixed {
+            order,
+            residual,
+            plan,
+        });
+    }
+
+    let max_lpc_order = level.max_lpc_order.min(block_size - 1);
+    if max_lpc_order > 0 {
+        let precision = if bps <= 17 { 15 } else { 13 };
+        let orders = lpc_coefficients(&autocorrelation(samples, max_lpc_order), max_lpc_order);
+
+        // without an exhaustive search only the order with the lowest estimated size is tried
+        let candidates: Vec<usize> = if level.exhaustive {
+            (0..orders.len()).collect()
+        } else {
+            let estimate = |i: usize| {
+                let (_, error) = orders[i];
+                let per_sample = 0.5 * (error / block_size as f64).max(1e-9).log2();
+                (block_size - i - 1) as f64 * per_sample.max(0.0)
+                    + (i + 1) as f64 * (bps + precision) as f64
+            };
+            (0..orders.len())
+                .min_by(|&a, &b| estimate(a).total_cmp(&estimate(b)))
+                .into_iter()
+                .collect()
+        };
+
+        for i in candidates {
+            let Some((coefficients, shift)) = quantize(&orders[i].0, precision) else {
+                continue;
+            };
+            let Some(residual) = lpc_residual(samples, &coefficients, shift) else {
+                continue;
+            };
+            let plan = plan_residual(
+                &residual,
+                block_size,
+                coefficients.len(),
+                level.max_partition_order,
+            );
+            consider(Subframe::Lpc {
+                precision,
+                shift,
+                coefficients,
+                residual,
+                plan,
+            });
+        }
+    }
+
+    best
+}
+
+fn write_subframe(writer: &mut BitWriter, subframe: &Subframe, samples: &[i64], bps: u32) {
+    let block_size = samples.len();
+
+    match subframe {
+        Subframe::Constant(value) => {
+            writer.write(0, 8);
+            writer.write_signed(*value, bps);
+        }
+
+        Subframe::Verbatim => {
+            writer.write(0b0000_0010, 8);
+            for &s in samples {
+                writer.write_signed(s, bps);
+            }
+        }
+
+        Subframe::Fixed {
+            order,
+            residual,
+            plan,
+        } => {
+            writer.write((0b00_1000 | *order as u64) << 1, 8);
+            for &s in &samples[..*order] {
+                writer.write_signed(s, bps);
+            }
+            write_residual(writer, plan, residual, block_size);
+        }
+
+        Subframe::Lpc {
+            precision,
+            shift,
+            coefficients,
+            residual,
+            plan,
+        } => {
+            let order = coefficients.len();
+            writer.write((0b10_0000 | (order as u64 - 1)) << 1, 8);
+            for &s in &samples[..order] {
+                writer.write_signed(s, bps);
+            }
+            writer.write(*precision as u64 - 1, 4);
+            writer.write(*shift as u64, 5);
+            for &c in coefficients {
+                writer.write_signed(c, *precision);
+            }
+            write_residual(writer, plan, residual, block_size);
+        }
+    }
+}
+
+// Encodes one block, `channels` holds the samples of every channel separately
+fn write_frame(
+    output: &mut Vec<u8>,
+    channels: &[Vec<i64>],
+    frame_number: u64,
+    bps: u32,
+    level: &Level,
+) {
+    let block_size = channels[0].len();
+
+    // the channel assignment and what goes in each subframe, with its bit depth
+    let (assignment, subframes) = if channels.len() == 2 && level.decorrelate {
+        let (left, right) = (channels[0].clone(), channels[1].clone());
+        let side: Vec<i64> = left.iter().zip(&right).map(|(l, r)| l - r).collect();
+        let mid: Vec<i64> = left.iter().zip(&right).map(|(l, r)| (l + r) >> 1).collect();
+
+        let best = |samples: Vec<i64>, bps: u32| {
+            let (subframe, bits) = best_subframe(&samples, bps, level);
+            (samples, bps, subframe, bits)
+        };
+        let left = best(left, bps);
+        let right = best(right, bps);
+        let side = best(side, bps + 1);
+        let mid = best(mid, bps);
+
+        let options = [
+            (left.3 + right.3, 1),
+            (left.3 + side.3, 8),
+            (right.3 + side.3, 9),
+            (mid.3 + side.3, 10),
+        ];
+        match options.iter().min_by_key(|(bits, _)| *bits).map(|o| o.1) {
+            Some(8) => (8, vec![left, side]),
+            Some(9) => (9, vec![side, right]),
+            Some(10) => (10, vec![mid, side]),
+            _ => (1, vec![left, right]),
+        }
+    } else {
+        let subframes = channels
+            .iter()
+            .map(|samples| {
+                let (subframe, bits) = best_subframe(samples, bps, level);
+                (samples.clone(), bps, subframe, bits)
+            })
+            .collect();
+        (channels.len() as u64 - 1, subframes)
+    };
+
+    let mut writer = BitWriter::new();
+
+    // frame header
+    writer.write(0b1111_1111_1111_1000, 16); // sync code, fixed block size
+    writer.write(0b0111, 4); // block size stored at the end of the header
+    writer.write(0b0000, 4); // sample rate from STREAMINFO
+    writer.write(assignment, 4);
+    writer.write(
+        match bps {
+            8 => 0b001,
+            16 => 0b100,
+            _ => 0b110,
+        },
+        3,
+    );
+    writer.write(0, 1);
+    write_utf8(&mut writer, frame_number);
+    writer.write(block_size as u64 - 1, 16);
+    let crc = crc8(&writer.bytes);
+    writer.write(crc as u64, 8);
+
+    for (samples, bps, subframe, _) in &subframes {
+        write_subframe(&mut writer, subframe, samples, *bps);
+    }
+
+    writer.align();
+    let crc = crc16(&writer.bytes);
+    writer.write(crc as u64, 16);
+
+    output.extend_from_slice(&writer.bytes);
+}
+
+/// Encodes interleaved `samples` as a FLAC stream, with `metadata` as Vorbis comments
+pub fn encode(
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    settings: &FlacSettings,
+    metadata: &Metadata,
+) -> Result<Vec<u8>> {
+    settings.validate()?;
+
+    if !(1..=8).contains(&channels) {
+        return Err(Error::InvalidExportSettings(format!(
+            "FLAC can hold 1 to 8 channels, the clip has {}",
+            channels
+        )));
+    }
+
+    // the sample rate in STREAMINFO is 20 bits
+    if !(1..1 << 20).contains(&sample_rate) {
+        return Err(Error::InvalidExportSettings(format!(
+            "FLAC can't hold a sample rate of {}Hz",
+            sample_rate
+        )));
+    }
+
+    let level = Level::new(settings.compression_level);
+    let bps = settings.bits_per_sample as u32;
+
+    let channel_count = channels as usize;
+    let frames = samples.len() / channel_count;
+
+    let ints: Vec<i64> = dither::quantize(&samples[..frames * channel_count], bps, settings.dither)
+        .into_iter()
+        .map(i64::from)
+        .collect();
+
+    // the MD5 in STREAMINFO is over the samples as little endian integers
+    let mut md5 = md5::Context::new();
+    let bytes_per_sample = bps as usize / 8;
+    for s in &ints {
+        md5.consume(&s.to_le_bytes()[..bytes_per_sample]);
+    }
+
+    let mut frames_data = Vec::new();
+    let (mut min_frame, mut max_frame) = (u32::MAX, 0u32);
+
+    for (frame_number, block) in ints.chunks(level.block_size * channel_count).enumerate() {
+        let deinterleaved: Vec<Vec<i64>> = (0..channel_count)
+            .map(|c| {
+                block
+                    .iter()
+                    .skip(c)
+                    .step_by(channel_count)
+                    .copied()
+                    .collect()
+            })
+            .collect();
+
+        let start = frames_data.len();
+        write_frame(
+            &mut frames_data,
+            &deinterleaved,
+            frame_number as u64,
+            bps,
+            &level,
+        );
+
+        let size = (frames_data.len() - start) as u32;
+        min_frame = min_frame.min(size);
+        max_frame = max_frame.max(size);
+    }
+
+    let mut streaminfo = BitWriter::new();
+    streaminfo.write(level.block_size as u64, 16);
+    streaminfo.write(level.block_size as u64, 16);
+    streaminfo.write(if frames == 0 { 0 } else { min_frame as u64 }, 24);
+    streaminfo.write(max_frame as u64, 24);
+    streaminfo.write(sample_rate as u64, 20);
+    streaminfo.write(channels as u64 - 1, 3);
+    streaminfo.write(bps as u64 - 1, 5);
+    streaminfo.write(frames as u64 >> 32, 4);
+    streaminfo.write(frames as u64 & 0xFFFF_FFFF, 32);
+
+    let comments = metadata.vorbis_comment_block();
+    if comments.len() >= 1 << 24 {
+        return Err(Error::InvalidExportSettings(
+            "the notes are too long to fit in a FLAC file".to_string(),
+        ));
+    }
+
+    let mut output = Vec::with_capacity(46 + comments.len() + frames_data.len());
+    output.extend_from_slice(b"fLaC");
+    // type 0 (STREAMINFO), 34 bytes long
+    output.extend_from_slice(&[0x00, 0, 0, 34]);
+    output.extend_from_slice(&streaminfo.bytes);
+    output.extend_from_slice(&md5.finalize().0);
+    // last metadata block, type 4 (VORBIS_COMMENT)
+    output.push(0x84);
+    output.extend_from_slice(&(comments.len() as u32).to_be_bytes()[1..]);
+    output.extend_from_slice(&comments);
+    output.extend_from_slice(&frames_data);
+
+    Ok(output)
+}
+
+/// Writes interleaved `samples` to `path` as a FLAC file
+pub fn write(
+    path: impl AsRef<Path>,
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    settings: &FlacSettings,
+    metadata: &Metadata,
+) -> Result<()> {
+    let bytes = encode(samples, channels, sample_rate, settings, metadata)?;
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&bytes)?;
+    file.flush()?;
+
+    Ok(())
+}
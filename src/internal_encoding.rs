@@ -1,12 +1,25 @@
 #![allow(non_snake_case)]
 use crate::audio_clip::AudioClip;
+use crate::error::{Error, Result};
 use audiopus::{Application, Bitrate, Channels};
 use audiopus::{Error as OpusError, ErrorCode as OpusErrorCode, MutSignals, packet::Packet};
 use audiopus::{
     SampleRate,
     coder::{Decoder, Encoder},
 };
-use color_eyre::{Result, eyre::eyre};
+use serde::{Serialize, Serializer};
+
+/// Version of the encoding new clips are stored with, stored next to every clip
+pub const CODEC_VERSION: u32 = 2;
+
+/// First bytes of every v2 encoded clip
+pub const MAGIC: [u8; 4] = *b"OXYG";
+
+// magic, version, channels, sample rate, sample count, bitrate, frame size, application
+const HEADER_V2_LEN: usize = 4 + 1 + 1 + 4 + 4 + 4 + 2 + 1;
+
+// the largest packet opus will ever hand us
+pub(crate) const MAX_PACKET_LEN: usize = 4000;
 
 #[allow(dead_code)]
 pub fn encode_v0(samples: &[f32]) -> Vec<u8> {
@@ -42,10 +55,10 @@ pub fn decode_v0(bytes: &[u8]) -> Vec<f32> {
 /// Encode a clip into an Opus packet
 ///
 /// Format:
-///     -4 bytes, number of samples as u32 in big endian
-///    - for each packet
-///     -2 bytes, number of bytes in packet as u16 in big endian
-///     - the raw packet
+/// - 4 bytes, number of samples as u32 in big endian
+/// - for each packet
+///   - 2 bytes, number of bytes in packet as u16 in big endian
+///   - the raw packet
 pub fn encode_v1(clip: &AudioClip) -> Result<(u32, Vec<u8>)> {
     let sample_rate: i32 = clip.sample_rate.try_into()?;
     let resampled: AudioClip;
@@ -112,13 +125,22 @@ pub fn encode_v1(clip: &AudioClip) -> Result<(u32, Vec<u8>)> {
             }
 
             Err(e) => {
-                return Err(eyre!(e));
+                return Err(e.into());
             }
         }
     }
     output.truncate(output_i);
 
-    Ok((sample_rate as u32 as u32, output))
+    Ok((sample_rate as u32, output))
+}
+
+/// Reads the number of samples from the header of a v1 encoded clip
+/// without decoding anything
+pub fn sample_count_v1(bytes: &[u8]) -> Result<u32> {
+    match bytes.get(..4) {
+        Some(&[a, b, c, d]) => Ok(u32::from_be_bytes([a, b, c, d])),
+        _ => Err(Error::Decode("Not enough bytes to decode".to_string())),
+    }
 }
 
 pub fn decode_v1(sample_rate: u32, bytes: &[u8]) -> Result<Vec<f32>> {
@@ -131,7 +153,7 @@ pub fn decode_v1(sample_rate: u32, bytes: &[u8]) -> Result<Vec<f32>> {
 
     let mut bytes_i = 0;
     if bytes.len() < 4 {
-        return Err(eyre!("Not enough bytes to decode"));
+        return Err(Error::Decode("Not enough bytes to decode".to_string()));
     }
 
     let num_samples: usize =
@@ -146,17 +168,17 @@ pub fn decode_v1(sample_rate: u32, bytes: &[u8]) -> Result<Vec<f32>> {
             (Some(&a), Some(&b)) => u16::from_be_bytes([a, b]).into(),
 
             _ => {
-                return Err(eyre!("Not enough bytes to decode"));
+                return Err(Error::Decode("Not enough bytes to decode".to_string()));
             }
         };
         bytes_i += 2;
 
         if bytes_i + pkt_len > bytes.len() {
-            return Err(eyre!("Not enough bytes to decode"));
+            return Err(Error::Decode("Not enough bytes to decode".to_string()));
         }
 
         if samples_i + frame_size > samples.len() {
-            return Err(eyre!("Not enough samples to decode"));
+            return Err(Error::Decode("Not enough samples to decode".to_string()));
         }
 
         let actual_frame_size = decoder.decode_float(
@@ -166,8 +188,8 @@ pub fn decode_v1(sample_rate: u32, bytes: &[u8]) -> Result<Vec<f32>> {
         )?;
 
         if actual_frame_size != frame_size {
-            return Err(eyre!(
-                "Decoded frame size is not the same as the frame size"
+            return Err(Error::Decode(
+                "Decoded frame size is not the same as the frame size".to_string(),
             ));
         }
 
@@ -179,3 +201,492 @@ pub fn decode_v1(sample_rate: u32, bytes: &[u8]) -> Result<Vec<f32>> {
 
     Ok(samples)
 }
+
+/// How the encoder spends its bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BitrateMode {
+    /// Variable bitrate, the opus default
+    Vbr,
+    /// Variable bitrate that never goes over the bitrate
+    ConstrainedVbr,
+    /// Constant bitrate
+    Cbr,
+}
+
+impl BitrateMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BitrateMode::Vbr => "vbr",
+            BitrateMode::ConstrainedVbr => "constrained-vbr",
+            BitrateMode::Cbr => "cbr",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "vbr" => Some(BitrateMode::Vbr),
+            "constrained-vbr" => Some(BitrateMode::ConstrainedVbr),
+            "cbr" => Some(BitrateMode::Cbr),
+            _ => None,
+        }
+    }
+}
+
+/// Name of an opus application as it is stored and printed
+pub fn application_name(application: Application) -> &'static str {
+    match application {
+        Application::Audio => "audio",
+        Application::Voip => "voip",
+        Application::LowDelay => "low-delay",
+    }
+}
+
+pub fn application_from_name(name: &str) -> Option<Application> {
+    match name {
+        "audio" => Some(Application::Audio),
+        "voip" => Some(Application::Voip),
+        "low-delay" => Some(Application::LowDelay),
+        _ => None,
+    }
+}
+
+fn serialize_application<S: Serializer>(
+    application: &Application,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(application_name(*application))
+}
+
+/// The frame durations opus supports, in milliseconds
+pub const FRAME_DURATIONS_MS: [f32; 6] = [2.5, 5.0, 10.0, 20.0, 40.0, 60.0];
+
+/// How a clip gets encoded, these are stored next to every clip
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct EncoderSettings {
+    // bits per second
+    pub bitrate: u32,
+    // length of every packet, one of `FRAME_DURATIONS_MS`
+    pub frame_ms: f32,
+    // 0 to 10, higher is slower and sounds better
+    pub complexity: u8,
+    pub bitrate_mode: BitrateMode,
+    #[serde(serialize_with = "serialize_application")]
+    pub application: Application,
+}
+
+/// Ready made `EncoderSettings`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quality {
+    /// 12kbps, tuned for speech. Small, but you can hear it
+    Low,
+    /// 24kbps, what the OPUS documentation suggests for normal mono audio
+    Voice,
+    /// 64kbps, music sounds fine as well
+    High,
+    /// 128kbps, as close to the original as opus gets
+    Archival,
+}
+
+impl Quality {
+    pub fn settings(self) -> EncoderSettings {
+        let (bitrate, complexity, application) = match self {
+            Quality::Low => (12000, 5, Application::Voip),
+            Quality::Voice => (24000, 9, Application::Audio),
+            Quality::High => (64000, 10, Application::Audio),
+            Quality::Archival => (128000, 10, Application::Audio),
+        };
+
+        EncoderSettings {
+            bitrate,
+            frame_ms: 20.0,
+            complexity,
+            bitrate_mode: BitrateMode::Vbr,
+            application,
+        }
+    }
+}
+
+impl Default for EncoderSettings {
+    // what every clip was encoded with before the settings were configurable
+    fn default() -> Self {
+        Quality::Voice.settings()
+    }
+}
+
+impl EncoderSettings {
+    /// Checks the settings are something opus can actually do
+    pub fn validate(&self) -> Result<()> {
+        if !(6000..=510000).contains(&self.bitrate) {
+            return Err(Error::InvalidEncoderSettings(format!(
+                "bitrate has to be between 6000 and 510000 bits per second, got {}",
+                self.bitrate
+            )));
+        }
+
+        if !FRAME_DURATIONS_MS.contains(&self.frame_ms) {
+            return Err(Error::InvalidEncoderSettings(format!(
+                "frame duration has to be one of {:?} ms, got {}",
+                FRAME_DURATIONS_MS, self.frame_ms
+            )));
+        }
+
+        if self.complexity > 10 {
+            return Err(Error::InvalidEncoderSettings(format!(
+                "complexity has to be between 0 and 10, got {}",
+                self.complexity
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Everything needed to decode a v2 clip, stored at the start of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderV2 {
+    pub channels: u8,
+    pub sample_rate: u32,
+    // samples per channel
+    pub sample_count: u32,
+    // bits per second the encoder was asked for
+    pub bitrate: u32,
+    // samples per channel in every packet
+    pub frame_size: u16,
+    pub application: Application,
+}
+
+impl HeaderV2 {
+    /// Reads the header of a v2 encoded clip without decoding anything
+    pub fn read(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_V2_LEN || bytes[..4] != MAGIC {
+            return Err(Error::Decode("Not a v2 encoded clip".to_string()));
+        }
+
+        if bytes[4] != 2 {
+            return Err(Error::Decode(format!(
+                "Expected format version 2, found {}",
+                bytes[4]
+            )));
+        }
+
+        let u32_at =
+            |i: usize| u32::from_be_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+
+        Ok(Self {
+            channels: bytes[5],
+            sample_rate: u32_at(6),
+            sample_count: u32_at(10),
+            bitrate: u32_at(14),
+            frame_size: u16::from_be_bytes([bytes[18], bytes[19]]),
+            application: match bytes[20] {
+                0 => Application::Audio,
+                1 => Application::Voip,
+                2 => Application::LowDelay,
+                other => {
+                    return Err(Error::Decode(format!("Unknown application {}", other)));
+                }
+            },
+        })
+    }
+
+    fn write(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(&MAGIC);
+        output.push(2);
+        output.push(self.channels);
+        output.extend_from_slice(&self.sample_rate.to_be_bytes());
+        output.extend_from_slice(&self.sample_count.to_be_bytes());
+        output.extend_from_slice(&self.bitrate.to_be_bytes());
+        output.extend_from_slice(&self.frame_size.to_be_bytes());
+        output.push(match self.application {
+            Application::Audio => 0,
+            Application::Voip => 1,
+            Application::LowDelay => 2,
+        });
+    }
+}
+
+/// Encode a clip into a self describing blob of Opus packets
+///
+/// Format, all numbers in big endian:
+/// - 4 bytes, the magic `OXYG`
+/// - 1 byte, format version (2)
+/// - 1 byte, number of channels, 1 or 2
+/// - 4 bytes, sample rate
+/// - 4 bytes, number of samples per channel
+/// - 4 bytes, bitrate in bits per second
+/// - 2 bytes, samples per channel in every packet
+/// - 1 byte, opus application (0 audio, 1 voip, 2 low delay)
+/// - for each packet, holding `frame size` interleaved samples of every channel
+///   - 2 bytes, number of bytes in packet
+///   - 4 bytes, CRC32 of the packet
+///   - the raw packet
+/// - 4 bytes, CRC32 of everything before it
+pub fn encode_v2(clip: &AudioClip, settings: &EncoderSettings) -> Result<(u32, Vec<u8>)> {
+    settings.validate()?;
+    let opus_channels = opus_channels(clip.channels)?;
+
+    // clips at a rate opus can't do are resampled with the default quality,
+    // resample them beforehand with `opus_sample_rate` to pick another one
+    let resampled: AudioClip;
+    let samples = if opus_sample_rate(clip.sample_rate) == clip.sample_rate {
+        &clip.samples
+    } else {
+        resampled = clip.resample(opus_sample_rate(clip.sample_rate));
+        &resampled.samples
+    };
+    let sample_rate = opus_sample_rate(clip.sample_rate);
+
+    let channels = clip.channels as usize;
+    let header = HeaderV2 {
+        channels: clip.channels.try_into()?,
+        sample_rate,
+        sample_count: (samples.len() / channels).try_into()?,
+        bitrate: settings.bitrate,
+        frame_size: frame_size(sample_rate, settings),
+        application: settings.application,
+    };
+
+    let encoder = encoder(sample_rate, opus_channels, settings)?;
+
+    let mut output = Vec::with_capacity(HEADER_V2_LEN + samples.len() / 8);
+    header.write(&mut output);
+
+    // the samples are interleaved, so a frame holds `frame_size` samples of every channel
+    let frame_len = header.frame_size as usize * channels;
+    let mut frame = vec![0f32; frame_len];
+    let mut packet = [0u8; MAX_PACKET_LEN];
+
+    for chunk in samples.chunks(frame_len) {
+        // the last chunk is padded with silence to a full frame
+        frame[..chunk.len()].copy_from_slice(chunk);
+        frame[chunk.len()..].fill(0.0);
+
+        let pkt_len = encoder.encode_float(&frame, &mut packet)?;
+        write_packet_v2(&mut output, &packet[..pkt_len])?;
+    }
+
+    let checksum = crc32fast::hash(&output);
+    output.extend_from_slice(&checksum.to_be_bytes());
+
+    Ok((header.sample_rate, output))
+}
+
+/// Puts packets encoded elsewhere together into a v2 encoded clip, as `encode_v2` would have
+pub fn from_packets_v2<'a>(
+    header: &HeaderV2,
+    packets: impl IntoIterator<Item = &'a [u8]>,
+) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    header.write(&mut output);
+
+    for packet in packets {
+        write_packet_v2(&mut output, packet)?;
+    }
+
+    let checksum = crc32fast::hash(&output);
+    output.extend_from_slice(&checksum.to_be_bytes());
+
+    Ok(output)
+}
+
+// A packet as v2 stores it: its length, its CRC32 and the packet itself
+pub(crate) fn write_packet_v2(output: &mut Vec<u8>, packet: &[u8]) -> Result<()> {
+    output.extend_from_slice(&u16::try_from(packet.len())?.to_be_bytes());
+    output.extend_from_slice(&crc32fast::hash(packet).to_be_bytes());
+    output.extend_from_slice(packet);
+
+    Ok(())
+}
+
+/// Samples per channel in every packet at `sample_rate`
+pub fn frame_size(sample_rate: u32, settings: &EncoderSettings) -> u16 {
+    (sample_rate as f32 * settings.frame_ms / 1000.0) as u16
+}
+
+// An encoder set up the way `settings` say, `sample_rate` has to be one opus can do
+pub(crate) fn encoder(
+    sample_rate: u32,
+    channels: Channels,
+    settings: &EncoderSettings,
+) -> Result<Encoder> {
+    let sample_rate = SampleRate::try_from(i32::try_from(sample_rate)?)?;
+
+    let mut encoder = Encoder::new(sample_rate, channels, settings.application)?;
+    encoder.set_bitrate(Bitrate::BitsPerSecond(settings.bitrate.try_into()?))?;
+    encoder.set_complexity(settings.complexity)?;
+    match settings.bitrate_mode {
+        BitrateMode::Vbr => {
+            encoder.set_vbr(true)?;
+            encoder.set_vbr_constraint(false)?;
+        }
+        BitrateMode::ConstrainedVbr => {
+            encoder.set_vbr(true)?;
+            encoder.set_vbr_constraint(true)?;
+        }
+        BitrateMode::Cbr => encoder.set_vbr(false)?,
+    }
+
+    Ok(encoder)
+}
+
+/// The rate a clip recorded at `sample_rate` is stored at.
+/// Opus only does 8, 12, 16, 24 and 48kHz, anything else gets stored as 48kHz
+pub fn opus_sample_rate(sample_rate: u32) -> u32 {
+    match sample_rate {
+        8000 | 12000 | 16000 | 24000 | 48000 => sample_rate,
+        _ => 48000,
+    }
+}
+
+// Opus itself only does mono and stereo
+pub(crate) fn opus_channels(channels: u16) -> Result<Channels> {
+    match channels {
+        1 => Ok(Channels::Mono),
+        2 => Ok(Channels::Stereo),
+        other => Err(Error::UnsupportedChannels(other)),
+    }
+}
+
+/// The opus packets of a stored clip, in order, and what is needed to decode them
+pub struct OpusPackets<'a> {
+    pub header: HeaderV2,
+    pub packets: Vec<&'a [u8]>,
+}
+
+/// Splits a v2 encoded clip into its packets, failing if any of the checksums don't match
+pub fn packets_v2(bytes: &[u8]) -> Result<OpusPackets<'_>> {
+    let header = HeaderV2::read(bytes)?;
+
+    // the whole blob first, that also catches truncated clips
+    let (body, checksum) = bytes.split_at(bytes.len() - 4);
+    if crc32fast::hash(body).to_be_bytes() != checksum {
+        return Err(Error::Decode(
+            "Checksum mismatch, the clip is corrupt".to_string(),
+        ));
+    }
+
+    let mut packets = Vec::new();
+    let mut bytes_i = HEADER_V2_LEN;
+
+    while bytes_i < body.len() {
+        if bytes_i + 6 > body.len() {
+            return Err(Error::Decode("Not enough bytes to decode".to_string()));
+        }
+
+        let pkt_len = u16::from_be_bytes([body[bytes_i], body[bytes_i + 1]]) as usize;
+        let pkt_crc = &body[bytes_i + 2..bytes_i + 6];
+        bytes_i += 6;
+
+        let Some(packet) = body.get(bytes_i..bytes_i + pkt_len) else {
+            return Err(Error::Decode("Not enough bytes to decode".to_string()));
+        };
+
+        if crc32fast::hash(packet).to_be_bytes() != pkt_crc {
+            return Err(Error::Decode(format!(
+                "Checksum mismatch in packet {}, the clip is corrupt",
+                packets.len()
+            )));
+        }
+
+        packets.push(packet);
+        bytes_i += pkt_len;
+    }
+
+    Ok(OpusPackets { header, packets })
+}
+
+/// Splits a v1 encoded clip into its packets.
+/// v1 has no header, but it was always mono, 24kbps and 20ms frames
+pub fn packets_v1(sample_rate: u32, bytes: &[u8]) -> Result<OpusPackets<'_>> {
+    let header = HeaderV2 {
+        channels: 1,
+        sample_rate,
+        sample_count: sample_count_v1(bytes)?,
+        bitrate: 24000,
+        frame_size: (sample_rate / 1000 * 20).try_into()?,
+        application: Application::Audio,
+    };
+
+    let mut packets = Vec::new();
+    let mut bytes_i = 4;
+
+    while bytes_i < bytes.len() {
+        let pkt_len: usize = match (bytes.get(bytes_i), bytes.get(bytes_i + 1)) {
+            (Some(&a), Some(&b)) => u16::from_be_bytes([a, b]).into(),
+
+            _ => {
+                return Err(Error::Decode("Not enough bytes to decode".to_string()));
+            }
+        };
+        bytes_i += 2;
+
+        let Some(packet) = bytes.get(bytes_i..bytes_i + pkt_len) else {
+            return Err(Error::Decode("Not enough bytes to decode".to_string()));
+        };
+
+        packets.push(packet);
+        bytes_i += pkt_len;
+    }
+
+    Ok(OpusPackets { header, packets })
+}
+
+/// The packets of a stored clip, whatever version it was encoded with
+pub fn packets(codec_version: u32, sample_rate: u32, bytes: &[u8]) -> Result<OpusPackets<'_>> {
+    match codec_version {
+        1 => packets_v1(sample_rate, bytes),
+        2 => packets_v2(bytes),
+        other => Err(Error::Decode(format!("Unknown codec version {}", other))),
+    }
+}
+
+/// Decode a v2 encoded clip, failing if any of the checksums don't match.
+/// The samples of a stereo clip come back interleaved
+pub fn decode_v2(bytes: &[u8]) -> Result<Vec<f32>> {
+    let OpusPackets { header, packets } = packets_v2(bytes)?;
+
+    let sample_rate = SampleRate::try_from(i32::try_from(header.sample_rate)?)?;
+    let mut decoder = Decoder::new(sample_rate, opus_channels(header.channels.into())?)?;
+
+    let channels = header.channels as usize;
+    let frame_size = header.frame_size as usize;
+    let frame_len = frame_size * channels;
+    let sample_count = header.sample_count as usize * channels;
+    let mut samples = vec![0f32; sample_count + frame_len];
+    let mut samples_i = 0;
+
+    for packet in packets {
+        if samples_i + frame_len > samples.len() {
+            return Err(Error::Decode("Not enough samples to decode".to_string()));
+        }
+
+        let actual_frame_size = decoder.decode_float(
+            Some(Packet::try_from(packet)?),
+            MutSignals::try_from(&mut samples[samples_i..(samples_i + frame_len)])?,
+            false,
+        )?;
+
+        if actual_frame_size != frame_size {
+            return Err(Error::Decode(
+                "Decoded frame size is not the same as the frame size".to_string(),
+            ));
+        }
+
+        samples_i += frame_len;
+    }
+
+    // drop the silence the last frame was padded with
+    samples.truncate(samples_i.min(sample_count));
+
+    Ok(samples)
+}
+
+/// Decodes a stored clip with the decoder for the version it was encoded with
+pub fn decode(codec_version: u32, sample_rate: u32, bytes: &[u8]) -> Result<Vec<f32>> {
+    match codec_version {
+        1 => decode_v1(sample_rate, bytes),
+        2 => decode_v2(bytes),
+        other => Err(Error::Decode(format!("Unknown codec version {}", other))),
+    }
+}
@@ -0,0 +1,45 @@
+#![allow(non_snake_case)]
+//! Oxygen is a Voice Journal.
+//!
+//! The `Oxygen` binary is a thin command line client over this crate, so
+//! anything it can do can be done from your own tools as well.
+//!
+//! ```no_run
+//! use oxygen::Db;
+//!
+//! let db = Db::open()?;
+//! for entry in db.list(&[])? {
+//!     println!("{} {}", entry.clip_id, entry.clip_name);
+//! }
+//! # Ok::<(), oxygen::Error>(())
+//! ```
+
+pub mod audio_clip;
+pub mod config;
+pub mod controls;
+pub mod db;
+pub mod device;
+pub mod dither;
+pub mod error;
+pub mod flac;
+pub mod internal_encoding;
+pub mod metadata;
+pub mod ogg_opus;
+pub mod resample;
+pub mod spool;
+pub mod stream;
+pub mod transcribe;
+pub mod wav;
+
+pub use audio_clip::{AudioClip, Downmix, Marker, RecordLimits};
+pub use config::Config;
+pub use db::{ClipMeta, Db, SearchResult, TrashedClip};
+pub use device::DeviceChoice;
+pub use error::{Error, Result};
+pub use flac::FlacSettings;
+pub use internal_encoding::{BitrateMode, EncoderSettings, Quality};
+pub use metadata::Metadata;
+pub use resample::ResampleQuality;
+pub use spool::{Recovered, Spool, SpoolWriter};
+pub use transcribe::{Segment, Transcript};
+pub use wav::WavSettings;
@@ -1,53 +1,703 @@
 #![allow(non_snake_case)]
 
-mod audio_clip;
-mod db;
-mod internal_encoding;
-
 use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::time::Duration;
 
-use audio_clip::AudioClip;
 use chrono::prelude::*;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use color_eyre::{Result, eyre::eyre};
-use db::Db;
+use oxygen::{
+    AudioClip, BitrateMode, ClipMeta, Config, Db, DeviceChoice, Downmix, EncoderSettings,
+    FlacSettings, Metadata, Quality, RecordLimits, ResampleQuality, SearchResult, Spool,
+    Transcript, TrashedClip, WavSettings, device, device::Direction, internal_encoding,
+    internal_encoding::HeaderV2, internal_encoding::opus_sample_rate, ogg_opus, spool, transcribe,
+};
+use serde::Serialize;
 
 #[derive(Debug, Parser)]
 #[clap(name = "Oxygen")]
 #[clap(about = "Voice Journal Tool", long_about = None)]
 struct Cli {
+    /// Path to the journal database.
+    /// Defaults to `$OXYGEN_DB`, or `oxygen/oxygen.sqlite` in the user data directory
+    #[clap(long, global = true)]
+    db: Option<PathBuf>,
+
+    /// Path to the config file.
+    /// Defaults to `$OXYGEN_CONFIG`, or `oxygen/config.toml` in the user config directory
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Clips are deleted for good after being in the trash for this many days
+    #[clap(
+        long,
+        global = true,
+        env = "OXYGEN_TRASH_DAYS",
+        default_value_t = 30,
+        value_name = "DAYS"
+    )]
+    trash_days: u32,
+
+    /// How `list`, `info`, `search` and `trash list` print their results
+    #[clap(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+
+    /// How carefully clips are resampled when recording, importing and playing
+    #[clap(long, global = true, value_enum, default_value_t = ResampleQualityArg::Normal)]
+    resample_quality: ResampleQualityArg,
+
     #[clap(subcommand)]
     command: Commands,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ResampleQualityArg {
+    /// Shortest filter, rolls off the top of the band
+    Fast,
+    /// Good enough for anyone
+    Normal,
+    /// Longest filter, for archiving
+    Best,
+}
+
+impl From<ResampleQualityArg> for ResampleQuality {
+    fn from(quality: ResampleQualityArg) -> Self {
+        match quality {
+            ResampleQualityArg::Fast => ResampleQuality::Fast,
+            ResampleQualityArg::Normal => ResampleQuality::Normal,
+            ResampleQualityArg::Best => ResampleQuality::Best,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Human readable columns
+    Table,
+    /// A single JSON array (or object for `info`)
+    Json,
+    /// Comma separated values with a header row
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum QualityArg {
+    /// 12kbps, tuned for speech
+    Low,
+    /// 24kbps, good enough for a voice journal
+    Voice,
+    /// 64kbps, music sounds fine as well
+    High,
+    /// 128kbps, as close to the original as opus gets
+    Archival,
+}
+
+impl From<QualityArg> for Quality {
+    fn from(quality: QualityArg) -> Self {
+        match quality {
+            QualityArg::Low => Quality::Low,
+            QualityArg::Voice => Quality::Voice,
+            QualityArg::High => Quality::High,
+            QualityArg::Archival => Quality::Archival,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BitrateModeArg {
+    /// Variable bitrate
+    Vbr,
+    /// Variable bitrate that never goes over `--bitrate`
+    ConstrainedVbr,
+    /// Constant bitrate
+    Cbr,
+}
+
+impl From<BitrateModeArg> for BitrateMode {
+    fn from(mode: BitrateModeArg) -> Self {
+        match mode {
+            BitrateModeArg::Vbr => BitrateMode::Vbr,
+            BitrateModeArg::ConstrainedVbr => BitrateMode::ConstrainedVbr,
+            BitrateModeArg::Cbr => BitrateMode::Cbr,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ApplicationArg {
+    /// Best for music and mixed content
+    Audio,
+    /// Best for speech
+    Voip,
+    /// Lowest latency, worse quality
+    LowDelay,
+}
+
+impl From<ApplicationArg> for audiopus::Application {
+    fn from(application: ApplicationArg) -> Self {
+        match application {
+            ApplicationArg::Audio => audiopus::Application::Audio,
+            ApplicationArg::Voip => audiopus::Application::Voip,
+            ApplicationArg::LowDelay => audiopus::Application::LowDelay,
+        }
+    }
+}
+
+// How `record` and `import` encode the clip.
+// The preset is the starting point and any explicit option overrides it
+#[derive(Debug, Args)]
+struct EncoderArgs {
+    /// Encoder preset
+    #[clap(long, value_enum, default_value_t = QualityArg::Voice)]
+    quality: QualityArg,
+    /// Bitrate in bits per second, 6000 to 510000
+    #[clap(long)]
+    bitrate: Option<u32>,
+    /// Length of each opus frame: 2.5, 5, 10, 20, 40 or 60
+    #[clap(long, value_name = "MS")]
+    frame_ms: Option<f32>,
+    /// Encoder complexity, 0 to 10
+    #[clap(long)]
+    complexity: Option<u8>,
+    /// Variable, constrained variable or constant bitrate
+    #[clap(long, value_enum)]
+    bitrate_mode: Option<BitrateModeArg>,
+    /// What the encoder tunes for
+    #[clap(long, value_enum)]
+    application: Option<ApplicationArg>,
+}
+
+impl EncoderArgs {
+    fn settings(&self) -> Result<EncoderSettings> {
+        let mut settings = Quality::from(self.quality).settings();
+
+        if let Some(bitrate) = self.bitrate {
+            settings.bitrate = bitrate;
+        }
+        if let Some(frame_ms) = self.frame_ms {
+            settings.frame_ms = frame_ms;
+        }
+        if let Some(complexity) = self.complexity {
+            settings.complexity = complexity;
+        }
+        if let Some(mode) = self.bitrate_mode {
+            settings.bitrate_mode = mode.into();
+        }
+        if let Some(application) = self.application {
+            settings.application = application.into();
+        }
+
+        settings.validate()?;
+        Ok(settings)
+    }
+}
+
+// Which device `record` and `play` use, over what the config file says
+#[derive(Debug, Args)]
+struct DeviceArgs {
+    /// Audio host to use, like ALSA or JACK. `Oxygen devices` lists them
+    #[clap(long)]
+    host: Option<String>,
+    /// Name or index of the device, as `Oxygen devices` lists them
+    #[clap(long)]
+    device: Option<String>,
+    /// Open the device at this sample rate instead of its default one
+    #[clap(long, value_name = "HZ")]
+    rate: Option<u32>,
+    /// Save the host, device and rate in the config file for next time
+    #[clap(long)]
+    remember: bool,
+}
+
+impl DeviceArgs {
+    // The devices asked for, falling back to `saved` from the config file
+    fn choice(&self, saved: &DeviceChoice) -> DeviceChoice {
+        DeviceChoice {
+            host: self.host.clone(),
+            device: self.device.clone(),
+            rate: self.rate,
+        }
+        .or(saved.clone())
+    }
+}
+
+// When `record` stops by itself
+#[derive(Debug, Args)]
+struct LimitArgs {
+    /// Stop once the clip is this long: seconds, m:ss or h:mm:ss. Paused parts don't count
+    #[clap(long, value_parser = parse_time, value_name = "TIME")]
+    max_duration: Option<Duration>,
+    /// Stop once it has been silent for this long, the silence at the end is cut off
+    #[clap(long, value_parser = parse_time, value_name = "SECONDS")]
+    stop_on_silence: Option<Duration>,
+    /// Anything quieter than this counts as silence for `--stop-on-silence`
+    #[clap(
+        long,
+        value_name = "DBFS",
+        default_value_t = -50.0,
+        allow_negative_numbers = true,
+        requires = "stop_on_silence"
+    )]
+    silence_threshold: f32,
+    /// Stop and save the clip once the recording takes this much memory. When the device isn't
+    /// at a rate opus takes (like 44.1kHz) this counts the copy resampled for saving as well
+    #[clap(long, value_name = "MIB", default_value_t = 2048)]
+    max_memory: usize,
+}
+
+impl LimitArgs {
+    fn limits(&self) -> Result<RecordLimits> {
+        let limits = RecordLimits {
+            max_duration: self.max_duration,
+            stop_on_silence: self.stop_on_silence,
+            silence_threshold_db: self.silence_threshold,
+            max_bytes: self.max_memory.saturating_mul(1 << 20),
+        };
+
+        limits.validate()?;
+        Ok(limits)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CodecArg {
+    /// 32 bit float wav
+    Wav,
+    /// Lossless FLAC, see `--bits` and `--compression`
+    Flac,
+    /// The opus packets as they are stored, in an Ogg file
+    Opus,
+}
+
+impl CodecArg {
+    // The codec a path asks for by its extension
+    fn from_path(path: &str) -> Option<Self> {
+        match std::path::Path::new(path).extension()?.to_str()? {
+            "wav" => Some(CodecArg::Wav),
+            "flac" => Some(CodecArg::Flac),
+            "opus" => Some(CodecArg::Opus),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            CodecArg::Wav => "wav",
+            CodecArg::Flac => "flac",
+            CodecArg::Opus => "opus",
+        }
+    }
+}
+
+// How `export` and `export-all` write the files
+#[derive(Debug, Args)]
+struct ExportArgs {
+    /// File format, picked from the extension when not given.
+    /// (`--format` already sets how results are printed)
+    #[clap(long, value_enum)]
+    codec: Option<CodecArg>,
+    /// Bits per sample. 16, 24 or 32 for wav (32 unless `--int`), 8, 16 or 24 for FLAC (16)
+    #[clap(long)]
+    bits: Option<u8>,
+    /// Write wav samples as floats, 32 bits only
+    #[clap(long, conflicts_with = "int")]
+    float: bool,
+    /// Write wav samples as integers, 16 bits unless `--bits` says otherwise
+    #[clap(long)]
+    int: bool,
+    /// Round straight to the bit depth, without dither
+    #[clap(long)]
+    no_dither: bool,
+    /// Resample to this rate before writing
+    #[clap(long, value_name = "HZ")]
+    rate: Option<u32>,
+    /// FLAC compression level, 0 (fastest) to 8 (smallest)
+    #[clap(long, default_value_t = 5)]
+    compression: u8,
+}
+
+// A codec with everything it needs to write a file
+enum ExportFormat {
+    Wav(WavSettings),
+    Flac(FlacSettings),
+    Opus,
+}
+
+impl ExportArgs {
+    // `--codec`, or whatever the extension of the path says
+    fn codec_for(&self, path: &str) -> Result<CodecArg> {
+        self.codec
+            .or_else(|| CodecArg::from_path(path))
+            .ok_or_else(|| {
+                eyre!(
+                    "Can't tell the format from {}.\nUse a .wav, .flac or .opus path or pass --codec",
+                    path
+                )
+            })
+    }
+
+    fn format(&self, codec: CodecArg) -> Result<ExportFormat> {
+        if self.rate == Some(0) {
+            return Err(eyre!("--rate has to be above 0"));
+        }
+
+        match codec {
+            CodecArg::Wav => {
+                let bits_per_sample = self.bits.unwrap_or(if self.int { 16 } else { 32 });
+                let settings = WavSettings {
+                    bits_per_sample,
+                    float: self.float || (!self.int && bits_per_sample == 32),
+                    dither: !self.no_dither,
+                };
+
+                settings.validate()?;
+                Ok(ExportFormat::Wav(settings))
+            }
+
+            CodecArg::Flac => {
+                if self.float {
+                    return Err(eyre!("FLAC only holds integer samples, drop --float"));
+                }
+
+                let settings = FlacSettings {
+                    bits_per_sample: self.bits.unwrap_or(16),
+                    compression_level: self.compression,
+                    dither: !self.no_dither,
+                };
+
+                settings.validate()?;
+                Ok(ExportFormat::Flac(settings))
+            }
+
+            CodecArg::Opus => {
+                // the packets are copied as they are, so there is nothing to change
+                if self.bits.is_some() || self.float || self.int || self.rate.is_some() {
+                    return Err(eyre!(
+                        "--bits, --float, --int and --rate don't apply to .opus exports, the clip is copied as it is stored"
+                    ));
+                }
+
+                Ok(ExportFormat::Opus)
+            }
+        }
+    }
+}
+
+// Writes a decoded clip to `path`, resampled to `rate` if there is one
+fn write_clip(
+    clip: AudioClip,
+    path: &str,
+    format: &ExportFormat,
+    metadata: &Metadata,
+    rate: Option<u32>,
+    resample_quality: ResampleQuality,
+) -> Result<()> {
+    let clip = match rate {
+        Some(rate) => clip.resample_with(rate, resample_quality),
+        None => clip,
+    };
+
+    match format {
+        ExportFormat::Wav(settings) => clip.export_wav(path, settings, metadata)?,
+        ExportFormat::Flac(settings) => clip.export_flac(path, settings, metadata)?,
+        // there is nothing to copy, and no Ogg Opus encoder
+        ExportFormat::Opus => {
+            return Err(eyre!(
+                "Only whole stored clips can be exported as .opus, use wav or flac instead"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// Writes the clip with the name to `path`, or just the `range` of it,
+// tagged with its name, date, tags and notes
+fn export_clip(
+    db: &Db,
+    name: &str,
+    path: &str,
+    format: &ExportFormat,
+    range: Option<(Duration, Option<Duration>)>,
+    rate: Option<u32>,
+    resample_quality: ResampleQuality,
+) -> Result<()> {
+    if let (ExportFormat::Opus, None) = (format, range) {
+        // the stored packets go straight into the file, nothing is decoded
+        let (meta, bytes) = db.encoded(name)?;
+        let packets =
+            internal_encoding::packets(meta.clip_codec_version, meta.clip_sample_rate, &bytes)?;
+        ogg_opus::write(path, &packets, meta.clip_id as u32, &Metadata::from(&meta))?;
+        return Ok(());
+    }
+
+    let metadata = Metadata::from(&db.info(name)?);
+    let mut clip = db
+        .load(name)?
+        .ok_or_else(|| eyre!("No clip with the name {} found", name))?;
+
+    if let Some((from, to)) = range {
+        clip = clip.range(from, to)?;
+    }
+
+    write_clip(clip, path, format, &metadata, rate, resample_quality)
+}
+
+// Parses `90`, `1:30`, `00:01:30` or `1:30.5` into a duration
+fn parse_time(value: &str) -> std::result::Result<Duration, String> {
+    let invalid = || format!("`{}` isn't a time, use seconds, m:ss or h:mm:ss", value);
+
+    let mut parts = value.trim().rsplit(':');
+    let seconds: f64 = parts
+        .next()
+        .and_then(|part| part.parse().ok())
+        .filter(|seconds: &f64| seconds.is_finite() && *seconds >= 0.0)
+        .ok_or_else(invalid)?;
+
+    // minutes, then hours. The units go first so a part past the hours is left for the check below
+    let mut total = seconds;
+    for (unit, part) in [60.0, 3600.0].into_iter().zip(parts.by_ref()) {
+        let count: u64 = part.parse().map_err(|_| invalid())?;
+        total += count as f64 * unit;
+    }
+
+    if parts.next().is_some() {
+        return Err(invalid());
+    }
+
+    // a finite number can still be more seconds than a `Duration` holds
+    Duration::try_from_secs_f64(total).map_err(|_| invalid())
+}
+
+// The columns of a result type for `--format csv`.
+// JSON goes through serde, but csv can't flatten or hold lists so it gets its own
+trait CsvRecord {
+    fn csv_header() -> Vec<&'static str>;
+    fn csv_record(&self) -> Vec<String>;
+}
+
+impl CsvRecord for ClipMeta {
+    fn csv_header() -> Vec<&'static str> {
+        vec![
+            "id",
+            "name",
+            "date",
+            "tags",
+            "notes",
+            "sample_rate",
+            "channels",
+            "sample_count",
+            "size",
+            "codec_version",
+            "bitrate",
+            "frame_ms",
+            "complexity",
+            "bitrate_mode",
+            "application",
+        ]
+    }
+
+    fn csv_record(&self) -> Vec<String> {
+        vec![
+            self.clip_id.to_string(),
+            self.clip_name.clone(),
+            self.clip_date.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            self.clip_tags.join(","),
+            self.clip_notes.clone().unwrap_or_default(),
+            self.clip_sample_rate.to_string(),
+            self.clip_channels.to_string(),
+            self.clip_sample_count.to_string(),
+            self.clip_size.to_string(),
+            self.clip_codec_version.to_string(),
+            self.clip_encoder.bitrate.to_string(),
+            self.clip_encoder.frame_ms.to_string(),
+            self.clip_encoder.complexity.to_string(),
+            self.clip_encoder.bitrate_mode.as_str().to_string(),
+            oxygen::internal_encoding::application_name(self.clip_encoder.application).to_string(),
+        ]
+    }
+}
+
+impl CsvRecord for SearchResult {
+    fn csv_header() -> Vec<&'static str> {
+        let mut header = ClipMeta::csv_header();
+        header.extend(["snippet", "rank"]);
+        header
+    }
+
+    fn csv_record(&self) -> Vec<String> {
+        let mut record = self.meta.csv_record();
+        record.extend([self.snippet.clone(), self.rank.to_string()]);
+        record
+    }
+}
+
+impl CsvRecord for TrashedClip {
+    fn csv_header() -> Vec<&'static str> {
+        let mut header = ClipMeta::csv_header();
+        header.push("deleted_at");
+        header
+    }
+
+    fn csv_record(&self) -> Vec<String> {
+        let mut record = self.meta.csv_record();
+        record.push(self.deleted_at.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+        record
+    }
+}
+
+// Prints the results in one of the machine readable formats,
+// the table format is up to each command
+fn print_records<T: Serialize + CsvRecord>(records: &[T], format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Table => unreachable!("tables are printed by the commands themselves"),
+
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(records)?),
+
+        OutputFormat::Ndjson => {
+            for record in records {
+                println!("{}", serde_json::to_string(record)?);
+            }
+        }
+
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            writer.write_record(T::csv_header())?;
+            for record in records {
+                writer.write_record(record.csv_record())?;
+            }
+            writer.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Subcommand)]
 enum Commands {
-    /// Record the voice clip with the default input device untill `ctrl+c` is pressed
+    /// Record a voice clip with the default input device, unless `--device` or the config file pick another.
+    /// Space pauses and resumes, `m` adds a marker, `q` or Enter stop (`ctrl+c` when not in a terminal)
     Record {
         /// name of the audio clip to record, if not specified, the current date and time will be used
         name: Option<String>,
+        /// Transcribe the clip once the recording is done
+        #[clap(long)]
+        transcribe: bool,
+        /// Path to the whisper model used by `--transcribe`, defaults to `$OXYGEN_WHISPER_MODEL`
+        #[clap(long, requires = "transcribe")]
+        model: Option<PathBuf>,
+        /// Record the first two channels of the input device instead of only the first
+        #[clap(long)]
+        stereo: bool,
+        #[clap(flatten)]
+        encoder: EncoderArgs,
+        #[clap(flatten)]
+        device: DeviceArgs,
+        #[clap(flatten)]
+        limits: LimitArgs,
+    },
+
+    /// List the audio hosts, their input and output devices and what they can be opened with
+    Devices {},
+
+    /// Save the recordings a crash or power cut left unfinished, as far as they got
+    Recover {
+        /// Delete them instead of saving them
+        #[clap(long)]
+        discard: bool,
     },
 
     /// List all the audio clips in the database
-    List {},
+    List {
+        /// Only list the clips that have this tag, can be given more than once
+        #[clap(long = "tag")]
+        tags: Vec<String>,
+    },
+
+    /// Show the details of the clip with the specified name
+    #[clap(arg_required_else_help = true)]
+    Info {
+        /// Name of the audio clip
+        name: String,
+    },
+
+    /// Search the names, notes and tags of the clips, best matches first.
+    /// Supports `"exact phrases"` and `prefix*` queries, anything else is searched for as typed
+    #[clap(arg_required_else_help = true)]
+    Search {
+        /// What to search for
+        query: String,
+    },
+
+    /// Transcribe the clip offline with a whisper.cpp compatible GGML model and store the transcript
+    #[clap(arg_required_else_help = true)]
+    Transcribe {
+        /// Name of the audio clip to transcribe
+        clip: String,
+        /// Path to the model file, defaults to `$OXYGEN_WHISPER_MODEL`
+        #[clap(long, conflicts_with = "show")]
+        model: Option<PathBuf>,
+        /// Print the stored transcript instead of transcribing again
+        #[clap(long)]
+        show: bool,
+    },
+
+    /// Show or change the notes written for a clip.
+    /// Without `text` or `--edit` the current notes are printed
+    #[clap(arg_required_else_help = true)]
+    Note {
+        /// Name of the audio clip
+        clip: String,
+        /// The new notes, replacing the old ones. An empty string removes them
+        #[clap(conflicts_with = "edit")]
+        text: Option<String>,
+        /// Write the notes in `$EDITOR`
+        #[clap(long)]
+        edit: bool,
+    },
+
+    /// Add or remove tags on a clip
+    #[clap(subcommand)]
+    Tag(TagCommands),
 
     /// play the clip with the specified name
     #[clap(arg_required_else_help = true)]
     Play {
         /// Name of the audio clip to play
         name: String,
+        #[clap(flatten)]
+        device: DeviceArgs,
     },
 
     /// play the last recorded clip
-    PlayLast {},
+    PlayLast {
+        #[clap(flatten)]
+        device: DeviceArgs,
+    },
 
-    /// delete the clip with the specified name
+    /// rename the clip, everything attached to it is kept
+    #[clap(arg_required_else_help = true)]
+    Rename {
+        /// Current name of the audio clip
+        old: String,
+        /// The new name
+        new: String,
+    },
+
+    /// move the clip with the specified name to the trash
     #[clap(arg_required_else_help = true)]
     Delete {
         /// Name of the audio clip to delete
         name: String,
     },
 
+    /// List, restore or empty the deleted clips
+    #[clap(subcommand)]
+    Trash(TrashCommands),
+
     /// Takes a path and a name and imports the file to the database
     #[clap(arg_required_else_help = true)]
     Import {
@@ -55,91 +705,716 @@ enum Commands {
         path: String,
         /// name of the file to import
         name: Option<String>,
+        /// Keep the first two channels of the file instead of downmixing it to mono
+        #[clap(long, conflicts_with = "downmix")]
+        stereo: bool,
+        /// How the channels are mixed into one: average, left, right, max-energy
+        /// or the index of a channel, starting at 0
+        #[clap(long, default_value = "average", value_name = "STRATEGY")]
+        downmix: Downmix,
+        #[clap(flatten)]
+        encoder: EncoderArgs,
     },
 
-    /// Export the clip with the specified name to the specified path
-    /// as a wav file
+    /// Export the clip with the specified name to the specified path.
+    /// The extension picks the format: `.wav`, `.flac`, or `.opus` for the clip as it is stored
     #[clap(arg_required_else_help = true)]
     Export {
         /// Name of the audio clip to export
         name: String,
         /// Name of the path as a unicode string
         path: String,
+        /// Start the file this far into the clip: seconds, m:ss or h:mm:ss
+        #[clap(long, value_parser = parse_time, value_name = "TIME")]
+        from: Option<Duration>,
+        /// End the file this far into the clip, the end of the clip if it is past it
+        #[clap(long, value_parser = parse_time, value_name = "TIME")]
+        to: Option<Duration>,
+        #[clap(flatten)]
+        export: ExportArgs,
+    },
+
+    /// Join clips one after the other into a single file, in the order they are given.
+    /// They are resampled to the highest sample rate among them
+    #[clap(arg_required_else_help = true)]
+    ExportConcat {
+        /// Path of the file to write, `.wav` or `.flac`
+        path: String,
+        /// Names of the clips to join
+        #[clap(required = true)]
+        clips: Vec<String>,
+        /// Silence between the clips: seconds, m:ss or h:mm:ss
+        #[clap(long, value_parser = parse_time, value_name = "TIME", default_value = "0")]
+        gap: Duration,
+        #[clap(flatten)]
+        export: ExportArgs,
     },
 
     /// Exports all the clips in the database to the specified path
-    /// of the folder to export the wav files
+    /// of the folder to export the files, wav unless `--codec` says otherwise
     #[clap(arg_required_else_help = true)]
-    ExportAll { folder: String },
+    ExportAll {
+        folder: String,
+        #[clap(flatten)]
+        export: ExportArgs,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum TrashCommands {
+    /// List the clips in the trash
+    List {},
+
+    /// Take the clip with the specified name back out of the trash
+    #[clap(arg_required_else_help = true)]
+    Restore {
+        /// Name of the audio clip to restore
+        name: String,
+    },
+
+    /// Delete everything in the trash for good
+    Empty {},
+}
+
+#[derive(Debug, Subcommand)]
+enum TagCommands {
+    /// Tag the clip with the specified name
+    #[clap(arg_required_else_help = true)]
+    Add {
+        /// Name of the audio clip to tag
+        clip: String,
+        /// The tag to add
+        tag: String,
+    },
+
+    /// Remove a tag from the clip with the specified name
+    #[clap(arg_required_else_help = true)]
+    Remove {
+        /// Name of the audio clip to untag
+        clip: String,
+        /// The tag to remove
+        tag: String,
+    },
+}
+
+// Longest a note can get in `list` before it is cut off
+const NOTE_PREVIEW_LEN: usize = 30;
+
+// Only the first line of the note, cut down to fit in the `list` table
+fn note_preview(notes: &str) -> String {
+    let line = notes.lines().next().unwrap_or_default();
+
+    if line.chars().count() > NOTE_PREVIEW_LEN || notes.lines().nth(1).is_some() {
+        let cut: String = line.chars().take(NOTE_PREVIEW_LEN - 3).collect();
+        format!("{}...", cut)
+    } else {
+        line.to_string()
+    }
+}
+
+// Opens `$VISUAL`/`$EDITOR` on a temporary file holding `initial` and returns what was saved
+fn edit_in_editor(initial: &str) -> Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| {
+            if cfg!(windows) {
+                "notepad".to_string()
+            } else {
+                "vi".to_string()
+            }
+        });
+
+    // editors like `code --wait` come with their own arguments
+    let mut editor_args = editor.split_whitespace();
+    let program = editor_args
+        .next()
+        .ok_or_else(|| eyre!("$EDITOR is empty"))?;
+
+    // made with a random name that nobody else can have taken, and deleted when it is dropped
+    let file = tempfile::Builder::new()
+        .prefix("oxygen-note-")
+        .suffix(".txt")
+        .tempfile()?;
+    std::fs::write(file.path(), initial)?;
+
+    let status = std::process::Command::new(program)
+        .args(editor_args)
+        .arg(file.path())
+        .status();
+
+    // read back through the path, some editors replace the file instead of writing to it
+    let text = std::fs::read_to_string(file.path());
+
+    if !status?.success() {
+        return Err(eyre!("{} exited with an error, notes were not changed", program));
+    }
+
+    Ok(text?)
+}
+
+// The model given on the command line, or the one from the environment
+fn whisper_model(model: Option<PathBuf>) -> Result<PathBuf> {
+    // checked here so `record --transcribe` gives up before recording anything
+    if !cfg!(feature = "transcribe") {
+        return Err(eyre!(
+            "Oxygen was built without the `transcribe` feature.\nRebuild it with `--features transcribe` to transcribe clips"
+        ));
+    }
+
+    model
+        .or_else(|| std::env::var_os(transcribe::MODEL_ENV_VAR).map(PathBuf::from))
+        .ok_or_else(|| {
+            eyre!(
+                "No whisper model given.\nPass `--model <path>` or set {}",
+                transcribe::MODEL_ENV_VAR
+            )
+        })
+}
+
+// Formats a duration as `h:mm:ss`, or `m:ss` for anything shorter than an hour
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+// Formats a number of bytes as B, KiB or MiB
+fn format_size(bytes: u64) -> String {
+    const KIB: f64 = 1024.0;
+    let bytes_f = bytes as f64;
+
+    if bytes_f >= KIB * KIB {
+        format!("{:.1} MiB", bytes_f / (KIB * KIB))
+    } else if bytes_f >= KIB {
+        format!("{:.1} KiB", bytes_f / KIB)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+// Formats milliseconds as `mm:ss.mmm`
+fn timestamp(ms: u64) -> String {
+    format!("{:02}:{:02}.{:03}", ms / 60_000, (ms / 1000) % 60, ms % 1000)
+}
+
+fn print_transcript(transcript: &Transcript) {
+    for segment in &transcript.segments {
+        println!(
+            "[{} -> {}] {}",
+            timestamp(segment.start_ms),
+            timestamp(segment.end_ms),
+            segment.text
+        );
+    }
+}
+
+// The device the arguments and the config file pick, saving the choice with `--remember`
+fn pick_device(
+    args: &DeviceArgs,
+    direction: Direction,
+    config_path: &std::path::Path,
+) -> Result<DeviceChoice> {
+    if args.rate == Some(0) {
+        return Err(eyre!("--rate has to be above 0"));
+    }
+
+    let mut config = Config::load(config_path)?;
+
+    let saved = match direction {
+        Direction::Input => &mut config.record,
+        Direction::Output => &mut config.play,
+    };
+    let choice = args.choice(saved);
+
+    if args.remember {
+        // only save what can actually be opened
+        device::device(direction, &choice)?;
+        *saved = choice.clone();
+        config.save(config_path)?;
+        eprintln!("Saved the device in {}", config_path.display());
+    }
+
+    Ok(choice)
+}
+
+// The name a recovered clip is saved under, the one it was recorded with if it is still free
+fn free_name(db: &Db, name: &str) -> Result<String> {
+    if !db.name_taken(name)? {
+        return Ok(name.to_string());
+    }
+
+    let mut n = 1;
+    loop {
+        let candidate = match n {
+            1 => format!("{} (recovered)", name),
+            n => format!("{} (recovered {})", name, n),
+        };
+        if !db.name_taken(&candidate)? {
+            return Ok(candidate);
+        }
+        n += 1;
+    }
+}
+
+// Saves the recording in a spool file and deletes the file, the file is kept if anything goes wrong
+fn recover_spool(db: &Db, path: &std::path::Path) -> Result<()> {
+    let recovered = spool::recover(path)?;
+
+    // it crashed after saving the clip but before deleting the file
+    if db
+        .info(&recovered.name)
+        .is_ok_and(|meta| meta.clip_date == recovered.date)
+    {
+        println!("{} was already saved", recovered.name);
+        std::fs::remove_file(path)?;
+        return Ok(());
+    }
+
+    if HeaderV2::read(&recovered.encoded)?.sample_count == 0 {
+        println!("{} has nothing recorded in it", recovered.name);
+        std::fs::remove_file(path)?;
+        return Ok(());
+    }
+
+    let name = free_name(db, &recovered.name)?;
+    db.save_encoded(
+        &name,
+        recovered.date,
+        &recovered.encoded,
+        &recovered.settings,
+    )?;
+    if !recovered.markers.is_empty() {
+        db.save_markers(&name, &recovered.markers)?;
+    }
+    std::fs::remove_file(path)?;
+
+    let info = db.info(&name)?;
+    println!(
+        "Recovered {} ({}){}",
+        name,
+        timestamp(info.duration().as_millis() as u64),
+        if recovered.cut_short {
+            ", the last moments were lost"
+        } else {
+            ""
+        }
+    );
+
+    Ok(())
+}
+
+fn print_devices() {
+    for host in device::list() {
+        println!(
+            "{}{}",
+            host.name,
+            if host.default { " (default)" } else { "" }
+        );
+
+        for (title, devices) in [("Input", &host.inputs), ("Output", &host.outputs)] {
+            println!("  {} devices", title);
+            if devices.is_empty() {
+                println!("    none");
+            }
+
+            for device in devices {
+                println!(
+                    "    {:>3}  {}{}",
+                    device.index,
+                    device.name,
+                    if device.default { " (default)" } else { "" }
+                );
+
+                for config in &device.configs {
+                    let rate = if config.min_rate == config.max_rate {
+                        format!("{} Hz", config.min_rate)
+                    } else {
+                        format!("{}-{} Hz", config.min_rate, config.max_rate)
+                    };
+                    println!(
+                        "         {} ch, {}, {}",
+                        config.channels, rate, config.sample_format
+                    );
+                }
+            }
+        }
+    }
 }
 
 fn main() -> Result<()> {
     color_eyre::install()?;
     let args = Cli::parse();
-    let db = Db::open()?;
+    let format = args.format;
+    let resample_quality = ResampleQuality::from(args.resample_quality);
+    let config_path = args.config.clone().unwrap_or_else(Config::default_path);
+    let db = match &args.db {
+        Some(path) => Db::open_at(path)?,
+        None => Db::open()?,
+    };
+    let spool_dir = spool::spool_dir(args.db.clone().unwrap_or_else(Db::default_path));
+
+    let purged = db.purge_trash(chrono::Duration::days(args.trash_days.into()))?;
+    if purged > 0 {
+        eprintln!(
+            "Deleted {} clip(s) that were in the trash for more than {} days",
+            purged, args.trash_days
+        );
+    }
+
+    if !matches!(args.command, Commands::Recover { .. }) {
+        let left = spool::spooled(&spool_dir)?.len();
+        if left > 0 {
+            eprintln!(
+                "{} recording(s) did not finish, use `Oxygen recover` to save them",
+                left
+            );
+        }
+    }
 
     match args.command {
-        Commands::Record { name } => {
+        Commands::Record {
+            name,
+            transcribe,
+            model,
+            stereo,
+            encoder,
+            device,
+            limits,
+        } => {
             let name = name.unwrap_or_else(|| Local::now().format("%Y-%m-%d_%H-%M-%S").to_string());
 
-            if db.load(&name)?.is_some() {
-                return Err(eyre!(
-                    "Clip with this name already exists. Please rename the clip"
-                ));
+            if db.name_taken(&name)? {
+                return Err(if db.in_trash(&name)? {
+                    eyre!(
+                        "A clip with this name is in the trash. Restore it or empty the trash first"
+                    )
+                } else {
+                    eyre!("Clip with this name already exists. Please rename the clip")
+                });
             }
 
-            let mut clip = AudioClip::record(name)?;
+            // check everything before recording, not after
+            let settings = encoder.settings()?;
+            let limits = limits.limits()?;
+            let model = if transcribe {
+                Some(whisper_model(model)?)
+            } else {
+                None
+            };
+            let choice = pick_device(&device, Direction::Input, &config_path)?;
+
+            // written as it goes, so a crash doesn't lose the recording
+            let spool = Spool::new(&spool_dir, &settings, resample_quality);
+
+            let (clip, markers) =
+                AudioClip::record_on(name, stereo, &choice, &limits, Some(&spool))?;
+            let mut clip = clip.resample_with(opus_sample_rate(clip.sample_rate), resample_quality);
 
-            db.save(&mut clip)?;
+            db.save_with(&mut clip, &settings)?;
+            if !markers.is_empty() {
+                db.save_markers(&clip.name, &markers)?;
+            }
+            spool.remove()?;
+
+            if let Some(model) = model {
+                println!("Transcribing...");
+                let transcript = transcribe::transcribe(&clip, &model)?;
+                db.save_transcript(&clip.name, &transcript)?;
+                print_transcript(&transcript);
+            }
+        }
+
+        Commands::Devices {} => print_devices(),
+
+        Commands::Recover { discard } => {
+            let paths = spool::spooled(&spool_dir)?;
+            if paths.is_empty() {
+                println!("Nothing to recover");
+            }
+
+            for path in paths {
+                if discard {
+                    std::fs::remove_file(&path)?;
+                    println!("Deleted {}", path.display());
+                    continue;
+                }
+
+                if let Err(err) = recover_spool(&db, &path) {
+                    eprintln!(
+                        "Could not recover {}, the file is kept: {}",
+                        path.display(),
+                        err
+                    );
+                }
+            }
         }
 
-        Commands::List {} => {
+        Commands::List { tags } => {
+            let entries = db.list(&tags)?;
+
+            if format != OutputFormat::Table {
+                print_records(&entries, format)?;
+                return Ok(());
+            }
+
             println!(
-                "{id:>5}  {name:30} {date:30}",
+                "{id:>5}  {name:30} {date:20} {duration:>9} {size:>10}  {tags:20} Notes",
                 id = "ID",
                 name = "Name",
-                date = "Date"
+                date = "Date",
+                duration = "Duration",
+                size = "Size",
+                tags = "Tags"
             );
 
-            for entry in db.list()? {
+            for entry in entries {
                 // ? the DateTime struct will print the date and time in the format
                 // ? "%Y-%m-%d %H:%M:%S"
                 println!(
-                    "{:5}  {:30} {:30}",
+                    "{:5}  {:30} {:20} {:>9} {:>10}  {:20} {}",
                     entry.clip_id,
                     entry.clip_name,
                     entry
                         .clip_date
                         .with_timezone(&Local)
                         .format("%Y-%m-%d %H:%M:%S")
-                        .to_string()
+                        .to_string(),
+                    format_duration(entry.duration()),
+                    format_size(entry.clip_size),
+                    entry.clip_tags.join(", "),
+                    entry
+                        .clip_notes
+                        .as_deref()
+                        .map(note_preview)
+                        .unwrap_or_default()
+                );
+            }
+        }
+
+        Commands::Info { name } => {
+            let info = db.info(&name)?;
+
+            match format {
+                OutputFormat::Table => (),
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&info)?);
+                    return Ok(());
+                }
+                _ => {
+                    print_records(std::slice::from_ref(&info), format)?;
+                    return Ok(());
+                }
+            }
+
+            println!("{:12} {}", "Name", info.clip_name);
+            println!("{:12} {}", "ID", info.clip_id);
+            println!(
+                "{:12} {}",
+                "Date",
+                info.clip_date
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+            );
+            println!("{:12} {}", "Duration", format_duration(info.duration()));
+            println!("{:12} {} samples", "Length", info.clip_sample_count);
+            println!("{:12} {} Hz", "Sample Rate", info.clip_sample_rate);
+            println!(
+                "{:12} {}",
+                "Channels",
+                match info.clip_channels {
+                    1 => "mono".to_string(),
+                    2 => "stereo".to_string(),
+                    channels => channels.to_string(),
+                }
+            );
+            println!("{:12} {}", "Size", format_size(info.clip_size));
+            println!("{:12} Opus, v{}", "Codec", info.clip_codec_version);
+            println!(
+                "{:12} {} kbps, {} ms, complexity {}, {}, {}",
+                "Encoder",
+                info.clip_encoder.bitrate as f64 / 1000.0,
+                info.clip_encoder.frame_ms,
+                info.clip_encoder.complexity,
+                info.clip_encoder.bitrate_mode.as_str(),
+                oxygen::internal_encoding::application_name(info.clip_encoder.application)
+            );
+            println!("{:12} {}", "Tags", info.clip_tags.join(", "));
+            println!(
+                "{:12} {}",
+                "Markers",
+                db.markers(&name)?
+                    .iter()
+                    .map(|marker| timestamp(marker.position_ms))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            println!(
+                "{:12} {}",
+                "Notes",
+                info.clip_notes.as_deref().unwrap_or_default()
+            );
+        }
+
+        Commands::Search { query } => {
+            let results = db.search(&query)?;
+
+            if format != OutputFormat::Table {
+                print_records(&results, format)?;
+                return Ok(());
+            }
+
+            println!(
+                "{id:>5}  {name:30} {date:20} Match",
+                id = "ID",
+                name = "Name",
+                date = "Date"
+            );
+
+            for result in results {
+                println!(
+                    "{:5}  {:30} {:20} {}",
+                    result.meta.clip_id,
+                    result.meta.clip_name,
+                    result
+                        .meta
+                        .clip_date
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M:%S")
+                        .to_string(),
+                    result.snippet.replace('\n', " ")
                 );
             }
         }
 
-        Commands::Play { name } => {
+        Commands::Transcribe { clip, model, show } => {
+            if show {
+                match db.transcript(&clip)? {
+                    Some(transcript) => print_transcript(&transcript),
+                    None => println!("{} has not been transcribed", clip),
+                }
+            } else if let Some(audio) = db.load(&clip)? {
+                let model = whisper_model(model)?;
+
+                println!("Transcribing...");
+                let transcript = transcribe::transcribe(&audio, &model)?;
+                db.save_transcript(&clip, &transcript)?;
+                print_transcript(&transcript);
+            } else {
+                return Err(eyre!("No clip with the name {} found", clip));
+            }
+        }
+
+        Commands::Note { clip, text, edit } => {
+            if edit {
+                let notes = db.notes(&clip)?.unwrap_or_default();
+                db.set_notes(&clip, Some(&edit_in_editor(&notes)?))?;
+            } else if let Some(text) = text {
+                db.set_notes(&clip, Some(&text))?;
+            } else if let Some(notes) = db.notes(&clip)? {
+                println!("{}", notes);
+            } else {
+                println!("{} has no notes", clip);
+            }
+        }
+
+        Commands::Tag(TagCommands::Add { clip, tag }) => {
+            db.add_tag(&clip, &tag)?;
+        }
+
+        Commands::Tag(TagCommands::Remove { clip, tag }) => {
+            db.remove_tag(&clip, &tag)?;
+        }
+
+        Commands::Play { name, device } => {
             if let Some(clip) = db.load(&name)? {
-                clip.play()?;
+                let choice = pick_device(&device, Direction::Output, &config_path)?;
+                clip.play_on(resample_quality, &choice)?;
             } else {
                 return Err(eyre!("No clip with the name {} found", name));
             }
         }
 
-        Commands::PlayLast {} => {
+        Commands::PlayLast { device } => {
             if let Some(clip) = db.load_last()? {
+                let choice = pick_device(&device, Direction::Output, &config_path)?;
                 println!("Playing Last Clip");
-                clip.play()?;
+                clip.play_on(resample_quality, &choice)?;
             } else {
                 return Err(eyre!("No Clip found Empty Database"));
             }
         }
 
+        Commands::Rename { old, new } => {
+            db.rename(&old, &new)?;
+        }
+
         Commands::Delete { name } => {
             db.delete(&name)?;
+            println!(
+                "Moved {} to the trash. Use `Oxygen trash restore {}` to get it back",
+                name, name
+            );
+        }
+
+        Commands::Trash(TrashCommands::List {}) => {
+            let entries = db.trash()?;
+
+            if format != OutputFormat::Table {
+                print_records(&entries, format)?;
+                return Ok(());
+            }
+
+            println!(
+                "{id:>5}  {name:30} {date:20} Deleted",
+                id = "ID",
+                name = "Name",
+                date = "Date"
+            );
+
+            for entry in entries {
+                println!(
+                    "{:5}  {:30} {:20} {}",
+                    entry.meta.clip_id,
+                    entry.meta.clip_name,
+                    entry
+                        .meta
+                        .clip_date
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M:%S")
+                        .to_string(),
+                    entry
+                        .deleted_at
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M:%S")
+                );
+            }
+        }
+
+        Commands::Trash(TrashCommands::Restore { name }) => {
+            db.restore(&name)?;
+        }
+
+        Commands::Trash(TrashCommands::Empty {}) => {
+            println!("Deleted {} clip(s) for good", db.empty_trash()?);
         }
 
-        Commands::Import { path, name } => {
-            let name = match name {
+        Commands::Import {
+            path,
+            name,
+            stereo,
+            downmix,
+            encoder,
+        } => {
+            let settings = encoder.settings()?;
+
+            let (clip, metadata) = AudioClip::import_with_metadata(String::new(), path.clone())?;
+
+            // the name given, the one the file was tagged with, or the name of the file
+            let name = match name.or_else(|| metadata.name.clone()) {
                 Some(name) => name,
 
                 None => std::path::Path::new(&path)
@@ -150,25 +1425,102 @@ fn main() -> Result<()> {
                     .to_string(),
             };
 
-            if db.load(&name)?.is_some() {
+            if db.name_taken(&name)? {
+                return Err(if db.in_trash(&name)? {
+                    eyre!(
+                        "A clip with this name is in the trash. Restore it or empty the trash first"
+                    )
+                } else {
+                    eyre!("Clip with this name already exists. Please rename the file")
+                });
+            }
+
+            let clip = AudioClip { name, ..clip };
+            let clip = if stereo {
+                clip.first_channels(2)
+            } else {
+                clip.downmix(downmix)?
+            };
+            let mut clip = clip.resample_with(opus_sample_rate(clip.sample_rate), resample_quality);
+            // along with whatever else an exported clip carried
+            db.save_with_metadata(&mut clip, &settings, &metadata)?;
+        }
+
+        Commands::Export {
+            name,
+            path,
+            from,
+            to,
+            export,
+        } => {
+            let codec = export.codec_for(&path)?;
+            let range = (from.is_some() || to.is_some()).then(|| (from.unwrap_or_default(), to));
+
+            if codec == CodecArg::Opus && range.is_some() {
                 return Err(eyre!(
-                    "Clip with this name already exists. Please rename the file"
+                    "--from and --to can't cut .opus exports, the clip is copied as it is stored. Export as wav or flac instead"
                 ));
             }
 
-            let mut clip = AudioClip::import(name, path)?;
-            db.save(&mut clip)?;
+            export_clip(
+                &db,
+                &name,
+                &path,
+                &export.format(codec)?,
+                range,
+                export.rate,
+                resample_quality,
+            )?;
         }
 
-        Commands::Export { name, path } => {
-            if let Some(clip) = db.load(&name)? {
-                clip.export(&path)?;
-            } else {
-                return Err(eyre!("No clip with the name {} found", name));
+        Commands::ExportConcat {
+            path,
+            clips,
+            gap,
+            export,
+        } => {
+            let format = export.format(export.codec_for(&path)?)?;
+
+            let mut loaded = Vec::with_capacity(clips.len());
+            let mut tags: Vec<String> = Vec::new();
+            for name in &clips {
+                let meta = db.info(name)?;
+                for tag in meta.clip_tags {
+                    if !tags.contains(&tag) {
+                        tags.push(tag);
+                    }
+                }
+
+                loaded.push(
+                    db.load(name)?
+                        .ok_or_else(|| eyre!("No clip with the name {} found", name))?,
+                );
             }
+
+            let joined = AudioClip::concat(&loaded, gap, resample_quality)?;
+
+            // named after the clips in it, with the date of the first one and the tags of all of them
+            let metadata = Metadata {
+                name: Some(clips.join(" + ")),
+                date: Some(joined.date),
+                tags,
+                notes: None,
+            };
+
+            write_clip(
+                joined,
+                &path,
+                &format,
+                &metadata,
+                export.rate,
+                resample_quality,
+            )?;
         }
 
-        Commands::ExportAll { folder } => {
+        Commands::ExportAll { folder, export } => {
+            let codec = export.codec.unwrap_or(CodecArg::Wav);
+            let format = export.format(codec)?;
+
             let path = std::path::Path::new(&folder);
 
             if !path.exists() {
@@ -186,29 +1538,35 @@ fn main() -> Result<()> {
                 ));
             }
 
-            for entry in db.list()? {
-                if let Some(clip) = db.load(&entry.clip_name)? {
-                    let safe_name = std::path::Path::new(&entry.clip_name)
-                        .file_name()
-                        .unwrap_or_else(|| OsStr::new("invalid"))
-                        .to_str()
-                        .ok_or_else(|| eyre!("Invalid path.\nNot valid utf8"))?
-                        .to_string();
+            for entry in db.list(&[])? {
+                let safe_name = std::path::Path::new(&entry.clip_name)
+                    .file_name()
+                    .unwrap_or_else(|| OsStr::new("invalid"))
+                    .to_str()
+                    .ok_or_else(|| eyre!("Invalid path.\nNot valid utf8"))?
+                    .to_string();
 
-                    let export_path = path.join(std::path::Path::new(&format!(
-                        "{}_{}.wav",
-                        safe_name, entry.clip_id
-                    )));
+                let export_path = path.join(std::path::Path::new(&format!(
+                    "{}_{}.{}",
+                    safe_name,
+                    entry.clip_id,
+                    codec.extension()
+                )));
 
-                    let export_path = export_path
-                        .as_path()
-                        .to_str()
-                        .ok_or_else(|| eyre!("Invalid path.\nNot valid utf8"))?;
+                let export_path = export_path
+                    .as_path()
+                    .to_str()
+                    .ok_or_else(|| eyre!("Invalid path.\nNot valid utf8"))?;
 
-                    clip.export(&export_path)?;
-                } else {
-                    return Err(eyre!("{} clip was removed during export", entry.clip_name));
-                }
+                export_clip(
+                    &db,
+                    &entry.clip_name,
+                    export_path,
+                    &format,
+                    None,
+                    export.rate,
+                    resample_quality,
+                )?;
             }
         }
     }
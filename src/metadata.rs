@@ -0,0 +1,180 @@
+#![allow(non_snake_case)]
+//! The details of a clip that go along with it in exported files.
+//!
+//! Wav files get a RIFF `LIST/INFO` chunk, FLAC and Ogg Opus files Vorbis comments.
+//! `import` reads them back so a clip that goes out and back in keeps its name, date, tags and notes.
+
+use crate::audio_clip::AudioClip;
+use crate::db::ClipMeta;
+use chrono::prelude::*;
+use symphonia::core::meta::{StandardTagKey, Tag};
+
+/// Written into the files as the software that made them
+pub(crate) const VENDOR: &str = concat!("Oxygen ", env!("CARGO_PKG_VERSION"));
+
+// Neither format has a field for keywords that everything agrees on, these are the usual ones
+const VORBIS_KEYWORDS: &str = "KEYWORDS";
+const RIFF_KEYWORDS: &str = "IKEY";
+
+/// Name, date, tags and notes of a clip, anything missing is left out of the file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    pub name: Option<String>,
+    pub date: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+    pub notes: Option<String>,
+}
+
+impl From<&ClipMeta> for Metadata {
+    fn from(meta: &ClipMeta) -> Self {
+        Self {
+            name: Some(meta.clip_name.clone()),
+            date: Some(meta.clip_date),
+            tags: meta.clip_tags.clone(),
+            notes: meta.clip_notes.clone(),
+        }
+    }
+}
+
+// A clip on its own only knows its name and date
+impl From<&AudioClip> for Metadata {
+    fn from(clip: &AudioClip) -> Self {
+        Self {
+            name: Some(clip.name.clone()),
+            date: Some(clip.date),
+            ..Default::default()
+        }
+    }
+}
+
+// Dates are written to the second, that is all the other tools show anyway
+fn format_date(date: &DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// Our own dates, and the plain days other tools tend to write
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.with_timezone(&Utc));
+    }
+
+    let day = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    Some(day.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+impl Metadata {
+    /// Whatever the tags of an imported file say about the clip.
+    /// Tags that mean nothing to a clip are skipped
+    pub fn from_tags<'a>(tags: impl IntoIterator<Item = &'a Tag>) -> Self {
+        let mut metadata = Self::default();
+
+        for tag in tags {
+            // RIFF INFO values keep the nul at the end
+            let value = tag.value.to_string();
+            let value = value.trim_end_matches('\0').trim();
+            if value.is_empty() {
+                continue;
+            }
+
+            match tag.std_key {
+                Some(StandardTagKey::TrackTitle) if metadata.name.is_none() => {
+                    metadata.name = Some(value.to_string());
+                }
+                Some(StandardTagKey::Date) if metadata.date.is_none() => {
+                    metadata.date = parse_date(value);
+                }
+                Some(StandardTagKey::Comment) if metadata.notes.is_none() => {
+                    metadata.notes = Some(value.to_string());
+                }
+                _ if tag.key.eq_ignore_ascii_case(VORBIS_KEYWORDS)
+                    || tag.key.eq_ignore_ascii_case(RIFF_KEYWORDS) =>
+                {
+                    // clip tags can't hold a `,`, so that is what they are joined with
+                    metadata.tags.extend(
+                        value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|tag| !tag.is_empty())
+                            .map(str::to_string),
+                    );
+                }
+                _ => (),
+            }
+        }
+
+        metadata
+    }
+
+    // The fields as Vorbis comments, `KEY=value`
+    fn vorbis_comments(&self) -> Vec<String> {
+        let mut comments = Vec::new();
+
+        if let Some(name) = &self.name {
+            comments.push(format!("TITLE={}", name));
+        }
+        if let Some(date) = &self.date {
+            comments.push(format!("DATE={}", format_date(date)));
+        }
+        for tag in &self.tags {
+            comments.push(format!("{}={}", VORBIS_KEYWORDS, tag));
+        }
+        if let Some(notes) = &self.notes {
+            comments.push(format!("COMMENT={}", notes));
+        }
+
+        comments
+    }
+
+    /// The body of a Vorbis comment header, as FLAC stores it and OpusTags after its magic
+    pub(crate) fn vorbis_comment_block(&self) -> Vec<u8> {
+        let comments = self.vorbis_comments();
+
+        let mut block = Vec::new();
+        block.extend_from_slice(&(VENDOR.len() as u32).to_le_bytes());
+        block.extend_from_slice(VENDOR.as_bytes());
+        block.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+        for comment in comments {
+            block.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            block.extend_from_slice(comment.as_bytes());
+        }
+
+        block
+    }
+
+    /// A whole RIFF `LIST` chunk of the `INFO` kind
+    pub(crate) fn riff_info_chunk(&self) -> Vec<u8> {
+        let mut fields: Vec<(&[u8; 4], String)> = vec![(b"ISFT", VENDOR.to_string())];
+
+        if let Some(name) = &self.name {
+            fields.push((b"INAM", name.clone()));
+        }
+        if let Some(date) = &self.date {
+            fields.push((b"ICRD", format_date(date)));
+        }
+        if !self.tags.is_empty() {
+            fields.push((b"IKEY", self.tags.join(", ")));
+        }
+        if let Some(notes) = &self.notes {
+            fields.push((b"ICMT", notes.clone()));
+        }
+
+        let mut info = b"INFO".to_vec();
+        for (id, value) in fields {
+            // nul terminated, and every chunk starts on an even byte
+            let mut value = value.into_bytes();
+            value.push(0);
+
+            info.extend_from_slice(id);
+            info.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            info.extend_from_slice(&value);
+            if value.len() % 2 == 1 {
+                info.push(0);
+            }
+        }
+
+        let mut chunk = b"LIST".to_vec();
+        chunk.extend_from_slice(&(info.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(&info);
+        chunk
+    }
+}
@@ -0,0 +1,120 @@
+#![allow(non_snake_case)]
+//! Writing stored clips out as Ogg Opus (`.opus`) files.
+//!
+//! The clips are already opus, so the packets are copied into the Ogg pages as they are
+//! and the file sounds exactly like the clip does in the journal.
+//! See RFC 7845 for the layout.
+
+use crate::error::Result;
+use crate::internal_encoding::OpusPackets;
+use crate::metadata::Metadata;
+use audiopus::{Channels, SampleRate, coder::Encoder};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+// Granule positions are always counted at 48kHz, whatever rate the clip is at
+const GRANULE_RATE: u64 = 48000;
+
+// how much audio goes in a page before starting a new one, a second keeps seeking cheap
+const PAGE_DURATION: u64 = GRANULE_RATE;
+
+// The identification header, the first packet of the stream
+fn opus_head(channels: u8, pre_skip: u16, input_sample_rate: u32) -> Vec<u8> {
+    let mut head = Vec::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    head.push(1); // version
+    head.push(channels);
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&input_sample_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // channel mapping family 0, mono or stereo
+    head
+}
+
+// The comment header, the second packet of the stream
+fn opus_tags(metadata: &Metadata) -> Vec<u8> {
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&metadata.vorbis_comment_block());
+    tags
+}
+
+// Samples (at 48kHz) the decoder has to throw away at the start.
+// The clips are stored without it, so ask an encoder set up the same way what its delay is
+fn pre_skip(packets: &OpusPackets) -> Result<u16> {
+    let header = &packets.header;
+    let channels = match header.channels {
+        1 => Channels::Mono,
+        _ => Channels::Stereo,
+    };
+    let sample_rate = SampleRate::try_from(i32::try_from(header.sample_rate)?)?;
+    let lookahead = Encoder::new(sample_rate, channels, header.application)?.lookahead()?;
+
+    Ok((lookahead as u64 * GRANULE_RATE / header.sample_rate as u64).try_into()?)
+}
+
+/// Writes the packets of a stored clip to `path` as an Ogg Opus file, without re-encoding them.
+/// `metadata` goes in the comment header.
+///
+/// `serial` tells the streams of a file apart, any number will do for a file with just one
+pub fn write(
+    path: impl AsRef<Path>,
+    packets: &OpusPackets,
+    serial: u32,
+    metadata: &Metadata,
+) -> Result<()> {
+    let header = &packets.header;
+    let pre_skip = pre_skip(packets)?;
+
+    let mut writer = PacketWriter::new(BufWriter::new(File::create(path)?));
+
+    // both headers get a page of their own
+    writer.write_packet(
+        opus_head(header.channels, pre_skip, header.sample_rate).into_boxed_slice(),
+        serial,
+        PacketWriteEndInfo::EndPage,
+        0,
+    )?;
+    // a clip without any audio ends right after the headers
+    let tags_end = if packets.packets.is_empty() {
+        PacketWriteEndInfo::EndStream
+    } else {
+        PacketWriteEndInfo::EndPage
+    };
+    writer.write_packet(opus_tags(metadata).into_boxed_slice(), serial, tags_end, 0)?;
+
+    let frame = header.frame_size as u64 * GRANULE_RATE / header.sample_rate as u64;
+    // the last packet is cut short so the file ends where the clip does and not on a frame.
+    // The clips are stored without room for the pre-skip at the end, so it can't be longer
+    // than what the packets hold either
+    let end =
+        pre_skip as u64 + header.sample_count as u64 * GRANULE_RATE / header.sample_rate as u64;
+
+    // the granule position counts every sample decoded so far, the pre-skip included
+    let mut granule = 0;
+    let mut page_start = 0;
+
+    for (i, packet) in packets.packets.iter().enumerate() {
+        granule += frame;
+
+        let info = if i + 1 == packets.packets.len() {
+            granule = granule.min(end);
+            PacketWriteEndInfo::EndStream
+        } else if granule - page_start >= PAGE_DURATION {
+            page_start = granule;
+            PacketWriteEndInfo::EndPage
+        } else {
+            PacketWriteEndInfo::NormalPacket
+        };
+
+        writer.write_packet(Box::from(*packet), serial, info, granule)?;
+    }
+
+    writer
+        .into_inner()
+        .into_inner()
+        .map_err(|e| e.into_error())?;
+
+    Ok(())
+}
@@ -0,0 +1,312 @@
+#![allow(non_snake_case)]
+//! Band limited sample rate conversion.
+//!
+//! Every output sample is a windowed sinc (Kaiser window) over the input around it.
+//! The cutoff follows the lower of the two rates, so downsampling filters out
+//! everything that would otherwise fold back as aliasing.
+
+use std::f64::consts::PI;
+
+/// How hard the resampler works, better quality means a longer filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResampleQuality {
+    /// Short filter, about 60dB of alias rejection and the top fifth of the band rolled off
+    Fast,
+    /// About 85dB of alias rejection, flat up to 40% of the lower sample rate
+    #[default]
+    Normal,
+    /// About 100dB of alias rejection, flat up to 45% of the lower sample rate
+    Best,
+}
+
+impl ResampleQuality {
+    // zero crossings of the sinc on each side, kaiser beta, cutoff as a fraction of the lower nyquist
+    fn params(self) -> (f64, f64, f64) {
+        match self {
+            ResampleQuality::Fast => (8.0, 6.0, 0.8),
+            ResampleQuality::Normal => (24.0, 8.6, 0.89),
+            ResampleQuality::Best => (64.0, 10.0, 0.95),
+        }
+    }
+}
+
+// More phases than this and the taps are worked out for every sample instead of up front
+const MAX_PHASES: usize = 1024;
+
+// Zeroth order modified bessel function of the first kind, for the kaiser window
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut k = 1.0;
+
+    while term > sum * 1e-12 {
+        term *= (x / (2.0 * k)).powi(2);
+        sum += term;
+        k += 1.0;
+    }
+
+    sum
+}
+
+struct Kernel {
+    zero_crossings: f64,
+    beta: f64,
+    i0_beta: f64,
+    // twice the cutoff in cycles per input sample, the sinc is stretched by this
+    scale: f64,
+    // taps on each side of the output sample
+    half_len: isize,
+}
+
+impl Kernel {
+    fn new(from: u32, to: u32, quality: ResampleQuality) -> Self {
+        let (zero_crossings, beta, cutoff) = quality.params();
+        let scale = cutoff * (to as f64 / from as f64).min(1.0);
+
+        Self {
+            zero_crossings,
+            beta,
+            i0_beta: bessel_i0(beta),
+            scale,
+            half_len: (zero_crossings / scale).ceil() as isize,
+        }
+    }
+
+    // weight of an input sample `distance` input samples away from the output sample
+    fn weight(&self, distance: f64) -> f64 {
+        let u = distance * self.scale;
+        if u.abs() >= self.zero_crossings {
+            return 0.0;
+        }
+
+        let sinc = if u == 0.0 {
+            1.0
+        } else {
+            (PI * u).sin() / (PI * u)
+        };
+        let r = u / self.zero_crossings;
+        let window = bessel_i0(self.beta * (1.0 - r * r).sqrt()) / self.i0_beta;
+
+        sinc * window
+    }
+
+    // the taps for an output sample `frac` of the way past an input sample,
+    // from `1 - half_len` to `half_len` input samples away.
+    // They are normalised so a constant signal stays the same
+    fn taps(&self, frac: f64) -> Vec<f32> {
+        let weights: Vec<f64> = (1 - self.half_len..=self.half_len)
+            .map(|k| self.weight(k as f64 - frac))
+            .collect();
+        let sum: f64 = weights.iter().sum();
+
+        weights.iter().map(|w| (w / sum) as f32).collect()
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+// Works out output frames of one conversion, for `resample` and `Resampler`
+struct Converter {
+    channels: usize,
+    // output frame `j` sits at input frame `j * step / phases`
+    step: u64,
+    phases: usize,
+    kernel: Kernel,
+    table: Option<Vec<Vec<f32>>>,
+}
+
+impl Converter {
+    fn new(channels: u16, from: u32, to: u32, quality: ResampleQuality) -> Self {
+        let divisor = gcd(from.into(), to.into());
+        let phases = (to as u64 / divisor) as usize;
+
+        let kernel = Kernel::new(from, to, quality);
+        let table = (phases <= MAX_PHASES).then(|| {
+            (0..phases)
+                .map(|phase| kernel.taps(phase as f64 / phases as f64))
+                .collect()
+        });
+
+        Self {
+            channels: channels.max(1) as usize,
+            step: from as u64 / divisor,
+            phases,
+            kernel,
+            table,
+        }
+    }
+
+    // the input frame output frame `j` sits at or just after
+    fn base(&self, j: usize) -> isize {
+        (j as u64 * self.step / self.phases as u64) as isize
+    }
+
+    // the first input frame output frame `j` needs
+    fn first(&self, j: usize) -> isize {
+        self.base(j) + 1 - self.kernel.half_len
+    }
+
+    // the last input frame output frame `j` needs
+    fn last(&self, j: usize) -> isize {
+        self.base(j) + self.kernel.half_len
+    }
+
+    // Writes output frame `j` to `output`. `samples` starts at input frame `offset`,
+    // and there are `frames` input frames in all, anything past them is silence
+    fn frame(&self, j: usize, samples: &[f32], offset: usize, frames: usize, output: &mut [f32]) {
+        let phase = (j as u64 * self.step % self.phases as u64) as usize;
+
+        let computed;
+        let taps = match &self.table {
+            Some(table) => &table[phase],
+            None => {
+                computed = self.kernel.taps(phase as f64 / self.phases as f64);
+                &computed
+            }
+        };
+
+        let first = self.first(j);
+        // only the taps that land inside the input
+        let start = (-first).max(0) as usize;
+        let end = taps.len().min((frames as isize - first).max(0) as usize);
+
+        for (channel, out) in output.iter_mut().enumerate().take(self.channels) {
+            let mut sum = 0f32;
+            for (t, tap) in taps.iter().enumerate().take(end).skip(start) {
+                let i = (first + t as isize) as usize - offset;
+                sum += tap * samples[i * self.channels + channel];
+            }
+            *out = sum;
+        }
+    }
+}
+
+/// Resamples interleaved `samples` with `channels` channels from `from` Hz to `to` Hz.
+///
+/// The output has `frames * to / from` frames and no delay, the signal is taken to be
+/// silent before and after the input
+pub fn resample(
+    samples: &[f32],
+    channels: u16,
+    from: u32,
+    to: u32,
+    quality: ResampleQuality,
+) -> Vec<f32> {
+    if from == to || from == 0 || to == 0 {
+        return samples.to_vec();
+    }
+
+    let converter = Converter::new(channels, from, to, quality);
+    let channels = converter.channels;
+    let frames = samples.len() / channels;
+    let out_frames = (frames as u64 * to as u64 / from as u64) as usize;
+
+    let mut output = vec![0f32; out_frames * channels];
+
+    for (j, out) in output.chunks_mut(channels).enumerate() {
+        converter.frame(j, samples, 0, frames, out);
+    }
+
+    output
+}
+
+/// Resamples like `resample`, a piece at a time, for audio that is still coming in.
+///
+/// Everything `process` and `finish` return put together is what `resample`
+/// makes of all the input at once
+pub struct Resampler {
+    // `None` when the rates are the same
+    converter: Option<Converter>,
+    from: u32,
+    to: u32,
+    // the input frames that are still needed, the first one is input frame `offset`
+    buffer: Vec<f32>,
+    offset: usize,
+    // input frames taken in so far
+    frames: usize,
+    // the next output frame
+    next: usize,
+}
+
+impl Resampler {
+    pub fn new(channels: u16, from: u32, to: u32, quality: ResampleQuality) -> Self {
+        let converter = (from != to && from != 0 && to != 0)
+            .then(|| Converter::new(channels, from, to, quality));
+
+        Self {
+            converter,
+            from,
+            to,
+            buffer: Vec::new(),
+            offset: 0,
+            frames: 0,
+            next: 0,
+        }
+    }
+
+    /// Takes in more interleaved samples and returns the output frames that are now complete
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        let Some(channels) = self.converter.as_ref().map(|c| c.channels) else {
+            return samples.to_vec();
+        };
+
+        self.buffer.extend_from_slice(samples);
+        self.frames += samples.len() / channels;
+
+        // the frames that don't need any input past what came in so far
+        let mut output = Vec::new();
+        while self
+            .converter
+            .as_ref()
+            .is_some_and(|c| c.last(self.next) < self.frames as isize)
+        {
+            self.push_frame(&mut output);
+        }
+
+        // let go of the input no output frame will need again
+        let needed = self
+            .converter
+            .as_ref()
+            .map_or(0, |c| c.first(self.next).max(0) as usize);
+        if needed > self.offset {
+            self.buffer.drain(..(needed - self.offset) * channels);
+            self.offset = needed;
+        }
+
+        output
+    }
+
+    /// The output frames still left once the input is over
+    pub fn finish(mut self) -> Vec<f32> {
+        if self.converter.is_none() {
+            return Vec::new();
+        }
+
+        let out_frames = (self.frames as u64 * self.to as u64 / self.from as u64) as usize;
+        let mut output = Vec::new();
+        while self.next < out_frames {
+            self.push_frame(&mut output);
+        }
+
+        output
+    }
+
+    fn push_frame(&mut self, output: &mut Vec<f32>) {
+        let Some(converter) = &self.converter else {
+            return;
+        };
+
+        let at = output.len();
+        output.resize(at + converter.channels, 0.0);
+        converter.frame(
+            self.next,
+            &self.buffer,
+            self.offset,
+            self.frames,
+            &mut output[at..],
+        );
+        self.next += 1;
+    }
+}
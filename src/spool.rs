@@ -0,0 +1,366 @@
+#![allow(non_snake_case)]
+//! Recordings written to disk as they happen, so a crash only loses the last moments.
+//!
+//! While recording the audio is encoded to opus as it comes in and every packet is appended
+//! to a spool file next to the database. The file is deleted once the clip is saved, so a spool
+//! file that is still around belongs to a recording that never finished, and `recover` reads it back.
+//!
+//! Format, numbers in big endian:
+//! - 4 bytes, the magic `OXSP`
+//! - 1 byte, format version (1)
+//! - 4 bytes, length of the header, then the header as JSON
+//! - records until the end of the file, each starting with a byte saying what it is
+//!   - 0, a packet: 2 bytes length, 4 bytes CRC32 of the packet, the packet
+//!   - 1, a marker: 8 bytes position in ms, 4 bytes CRC32 of the position
+//!
+//! A crash can leave the last record cut short, reading stops at the first one that doesn't check out.
+//! The file is locked while the recording goes on, so it isn't taken for one left behind.
+
+use crate::audio_clip::Marker;
+use crate::error::{Error, Result};
+use crate::internal_encoding::{
+    self, BitrateMode, EncoderSettings, HeaderV2, MAX_PACKET_LEN, application_from_name,
+    application_name, opus_sample_rate,
+};
+use crate::resample::{ResampleQuality, Resampler};
+use audiopus::coder::Encoder;
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Extension of spool files
+pub const SPOOL_EXTENSION: &str = "spool";
+
+const MAGIC: [u8; 4] = *b"OXSP";
+const VERSION: u8 = 1;
+
+const PACKET: u8 = 0;
+const MARKER: u8 = 1;
+
+// how often everything written so far is pushed all the way to the disk
+const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+// What the recording is, at the start of the file
+#[derive(Debug, Serialize, Deserialize)]
+struct SpoolHeader {
+    name: String,
+    date: DateTime<Utc>,
+    channels: u16,
+    // the rate the packets are encoded at
+    sample_rate: u32,
+    bitrate: u32,
+    frame_ms: f32,
+    complexity: u8,
+    bitrate_mode: String,
+    application: String,
+}
+
+impl SpoolHeader {
+    fn settings(&self) -> Result<EncoderSettings> {
+        let invalid = |what: &str| Error::Spool(format!("unknown {} in the header", what));
+
+        Ok(EncoderSettings {
+            bitrate: self.bitrate,
+            frame_ms: self.frame_ms,
+            complexity: self.complexity,
+            bitrate_mode: BitrateMode::from_name(&self.bitrate_mode)
+                .ok_or_else(|| invalid("bitrate mode"))?,
+            application: application_from_name(&self.application)
+                .ok_or_else(|| invalid("application"))?,
+        })
+    }
+}
+
+/// The folder recordings are spooled to for the database at `db`, next to it
+pub fn spool_dir(db: impl AsRef<Path>) -> PathBuf {
+    db.as_ref().with_extension(SPOOL_EXTENSION)
+}
+
+/// Where a recording is spooled to, and how it is encoded on the way
+#[derive(Debug, Clone)]
+pub struct Spool {
+    path: PathBuf,
+    settings: EncoderSettings,
+    quality: ResampleQuality,
+}
+
+impl Spool {
+    /// A spool file in `dir`, named after when it is made.
+    /// Nothing is written until the recording starts
+    pub fn new(
+        dir: impl AsRef<Path>,
+        settings: &EncoderSettings,
+        quality: ResampleQuality,
+    ) -> Self {
+        let file = format!(
+            "{}-{}.{}",
+            Utc::now().format("%Y%m%d-%H%M%S"),
+            std::process::id(),
+            SPOOL_EXTENSION
+        );
+
+        Self {
+            path: dir.as_ref().join(file),
+            settings: *settings,
+            quality,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Deletes the spool file, once the clip it holds is safely saved
+    pub fn remove(&self) -> Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Starts the file for a recording of `channels` channels at `sample_rate`,
+    /// it stays locked until the writer is dropped
+    pub fn writer(
+        &self,
+        name: &str,
+        date: DateTime<Utc>,
+        channels: u16,
+        sample_rate: u32,
+    ) -> Result<SpoolWriter> {
+        self.settings.validate()?;
+
+        let opus_rate = opus_sample_rate(sample_rate);
+        let encoder = internal_encoding::encoder(
+            opus_rate,
+            internal_encoding::opus_channels(channels)?,
+            &self.settings,
+        )?;
+
+        let header = serde_json::to_vec(&SpoolHeader {
+            name: name.to_string(),
+            date,
+            channels,
+            sample_rate: opus_rate,
+            bitrate: self.settings.bitrate,
+            frame_ms: self.settings.frame_ms,
+            complexity: self.settings.complexity,
+            bitrate_mode: self.settings.bitrate_mode.as_str().to_string(),
+            application: application_name(self.settings.application).to_string(),
+        })
+        .map_err(|err| Error::Spool(err.to_string()))?;
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = File::create(&self.path)?;
+        file.try_lock()
+            .map_err(|err| Error::Spool(err.to_string()))?;
+
+        let mut start = MAGIC.to_vec();
+        start.push(VERSION);
+        start.extend_from_slice(&u32::try_from(header.len())?.to_be_bytes());
+        start.extend_from_slice(&header);
+        file.write_all(&start)?;
+        file.sync_data()?;
+
+        Ok(SpoolWriter {
+            file,
+            resampler: Resampler::new(channels, sample_rate, opus_rate, self.quality),
+            encoder,
+            frame_len: internal_encoding::frame_size(opus_rate, &self.settings) as usize
+                * channels as usize,
+            pending: Vec::new(),
+            records: Vec::new(),
+            last_sync: Instant::now(),
+        })
+    }
+}
+
+/// Encodes a recording as it comes in and appends it to the spool file.
+/// Samples short of a whole packet wait for the next ones
+pub struct SpoolWriter {
+    file: File,
+    resampler: Resampler,
+    encoder: Encoder,
+    // interleaved samples in a packet
+    frame_len: usize,
+    // samples waiting for a whole packet
+    pending: Vec<f32>,
+    // records waiting to be written
+    records: Vec<u8>,
+    last_sync: Instant,
+}
+
+impl SpoolWriter {
+    /// Appends interleaved `samples`, as they came from the device
+    pub fn write(&mut self, samples: &[f32]) -> Result<()> {
+        let resampled = self.resampler.process(samples);
+        self.pending.extend_from_slice(&resampled);
+
+        let whole = self.pending.len() / self.frame_len * self.frame_len;
+        let mut packet = [0u8; MAX_PACKET_LEN];
+        for frame in self.pending[..whole].chunks(self.frame_len) {
+            let len = self.encoder.encode_float(frame, &mut packet)?;
+
+            self.records.push(PACKET);
+            internal_encoding::write_packet_v2(&mut self.records, &packet[..len])?;
+        }
+        self.pending.drain(..whole);
+
+        self.flush()
+    }
+
+    /// Appends a marker, at a position in the recording as it will be saved
+    pub fn marker(&mut self, marker: Marker) -> Result<()> {
+        let position = marker.position_ms.to_be_bytes();
+
+        self.records.push(MARKER);
+        self.records.extend_from_slice(&position);
+        self.records
+            .extend_from_slice(&crc32fast::hash(&position).to_be_bytes());
+
+        self.flush()
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.file.write_all(&self.records)?;
+        self.records.clear();
+
+        if self.last_sync.elapsed() >= SYNC_INTERVAL {
+            self.file.sync_data()?;
+            self.last_sync = Instant::now();
+        }
+
+        Ok(())
+    }
+}
+
+/// A recording read back from a spool file
+#[derive(Debug, Clone)]
+pub struct Recovered {
+    pub name: String,
+    pub date: DateTime<Utc>,
+    pub settings: EncoderSettings,
+    /// The audio as a v2 encoded clip, ready for `Db::save_encoded`
+    pub encoded: Vec<u8>,
+    pub markers: Vec<Marker>,
+    /// The file ended in the middle of a record, the last moments are lost
+    pub cut_short: bool,
+}
+
+// Whether a recording is still writing to the file
+fn in_use(path: &Path) -> bool {
+    File::open(path).is_ok_and(|file| matches!(file.try_lock(), Err(TryLockError::WouldBlock)))
+}
+
+/// The spool files left behind in `dir`, oldest first. A missing folder has none,
+/// and the ones of recordings still going are left out
+pub fn spooled(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == SPOOL_EXTENSION) && !in_use(&path) {
+            paths.push(path);
+        }
+    }
+
+    // the names start with the time they were made
+    paths.sort();
+    Ok(paths)
+}
+
+/// Reads the recording in a spool file, up to the first record that doesn't check out
+pub fn recover(path: impl AsRef<Path>) -> Result<Recovered> {
+    let bytes = std::fs::read(path)?;
+
+    if bytes.len() < 9 || bytes[..4] != MAGIC {
+        return Err(Error::Spool("not a spool file".to_string()));
+    }
+    if bytes[4] != VERSION {
+        return Err(Error::Spool(format!("unknown spool version {}", bytes[4])));
+    }
+
+    let header_len = u32::from_be_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]) as usize;
+    let header: SpoolHeader = bytes
+        .get(9..9 + header_len)
+        .and_then(|header| serde_json::from_slice(header).ok())
+        .ok_or_else(|| Error::Spool("the header is cut short or corrupt".to_string()))?;
+    let settings = header.settings()?;
+
+    let mut packets = Vec::new();
+    let mut markers = Vec::new();
+    let mut i = 9 + header_len;
+
+    let u32_at = |at: usize| {
+        bytes
+            .get(at..at + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    };
+
+    // each arm gives back where the next record starts, `None` if this one doesn't check out
+    while i < bytes.len() {
+        let next = match bytes[i] {
+            PACKET => bytes.get(i + 1..i + 3).and_then(|len| {
+                let len = u16::from_be_bytes([len[0], len[1]]) as usize;
+                let crc = u32_at(i + 3)?;
+                let packet = bytes.get(i + 7..i + 7 + len)?;
+
+                (crc32fast::hash(packet) == crc).then(|| {
+                    packets.push(packet);
+                    i + 7 + len
+                })
+            }),
+
+            MARKER => bytes.get(i + 1..i + 9).and_then(|position| {
+                let crc = u32_at(i + 9)?;
+
+                (crc32fast::hash(position) == crc).then(|| {
+                    let mut ms = [0u8; 8];
+                    ms.copy_from_slice(position);
+                    markers.push(Marker {
+                        position_ms: u64::from_be_bytes(ms),
+                    });
+                    i + 13
+                })
+            }),
+
+            _ => None,
+        };
+
+        match next {
+            Some(next) => i = next,
+            None => break,
+        }
+    }
+
+    let frame_size = internal_encoding::frame_size(header.sample_rate, &settings);
+    let encoded = internal_encoding::from_packets_v2(
+        &HeaderV2 {
+            channels: header.channels.try_into()?,
+            sample_rate: header.sample_rate,
+            sample_count: (packets.len() * frame_size as usize).try_into()?,
+            bitrate: settings.bitrate,
+            frame_size,
+            application: settings.application,
+        },
+        packets,
+    )?;
+
+    Ok(Recovered {
+        name: header.name,
+        date: header.date,
+        settings,
+        encoded,
+        markers,
+        cut_short: i < bytes.len(),
+    })
+}
@@ -0,0 +1,226 @@
+#![allow(non_snake_case)]
+//! What happens to the audio between the device streams and the threads around them.
+//!
+//! None of it needs a device, so it can be tried out on made-up chunks of audio.
+
+use crate::audio_clip::{AudioClip, Marker, RecordLimits};
+use crate::error::Result;
+use crate::spool::SpoolWriter;
+use cpal::Sample;
+use rtrb::{Consumer, Producer};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// What the input stream shares with the thread waiting for keys
+#[derive(Debug, Default)]
+pub struct RecordState {
+    /// samples that come in while paused are dropped
+    pub paused: AtomicBool,
+    /// times the ring was too full to take everything the device sent
+    pub overruns: AtomicUsize,
+}
+
+/// What the output stream shares with the thread feeding it
+#[derive(Debug, Default)]
+pub struct PlayState {
+    /// every sample is in the ring, so running out of them is the end and not an underrun
+    pub fed_all: AtomicBool,
+    pub done: AtomicBool,
+    /// times the ring ran out before the end
+    pub underruns: AtomicUsize,
+}
+
+/// What the input stream does with the `channels` channels the device sends:
+/// the first `kept` of them are passed on through the ring, they are kept and spooled outside of it
+pub fn send_input_data<T>(
+    input: &[T],
+    channels: u16,
+    kept: u16,
+    state: &RecordState,
+    tx: &mut Producer<f32>,
+) where
+    T: Sample,
+    f32: cpal::FromSample<T>,
+{
+    if state.paused.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let kept = kept as usize;
+    let frames = input.len() / channels as usize;
+    // only whole frames go in, what doesn't fit is lost
+    let room = tx.slots() / kept;
+    if room < frames {
+        state.overruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    if let Ok(chunk) = tx.write_chunk_uninit(frames.min(room) * kept) {
+        chunk.fill_from_iter(
+            input
+                .chunks_exact(channels.into())
+                .flat_map(|frame| &frame[..kept])
+                .map(|sample| sample.to_sample::<f32>()),
+        );
+    }
+}
+
+/// What the output stream does, the samples already have as many channels as the device
+pub fn write_output_data<T>(output: &mut [T], rx: &mut Consumer<f32>, state: &PlayState)
+where
+    T: Sample + cpal::FromSample<f32>,
+{
+    // checked before looking at the ring, so nothing fed before it is missed
+    let fed_all = state.fed_all.load(Ordering::Acquire);
+
+    let available = rx.slots().min(output.len());
+    if let Ok(chunk) = rx.read_chunk(available) {
+        let (first, second) = chunk.as_slices();
+        for (sample, &value) in output.iter_mut().zip(first.iter().chain(second)) {
+            *sample = Sample::from_sample(value);
+        }
+        chunk.commit_all();
+    }
+
+    if available < output.len() {
+        for sample in &mut output[available..] {
+            *sample = Sample::from_sample(0f32);
+        }
+
+        if fed_all {
+            state.done.store(true, Ordering::Release);
+        } else {
+            state.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Tops the ring up with as much of `samples` past `fed` as it has room for, and moves `fed` along
+pub fn feed(samples: &[f32], fed: &mut usize, tx: &mut Producer<f32>, state: &PlayState) {
+    let n = tx.slots().min(samples.len() - *fed);
+    if let Ok(chunk) = tx.write_chunk_uninit(n) {
+        chunk.fill_from_iter(samples[*fed..].iter().copied());
+        *fed += n;
+    }
+    if *fed == samples.len() {
+        state.fed_all.store(true, Ordering::Release);
+    }
+}
+
+/// Keeps what the input stream sends, on the thread waiting for keys
+pub struct Taker {
+    clip: AudioClip,
+    // nothing past this many frames is kept
+    max_frames: usize,
+    // frames at the end of the clip that are all below this
+    silence_level: f32,
+    silent_frames: usize,
+    spool: Option<SpoolWriter>,
+}
+
+impl Taker {
+    /// Keeps what is recorded after the samples `clip` already has, up to where `limits` stop it.
+    /// With a `spool` everything kept is written to it as well
+    pub fn new(clip: AudioClip, limits: &RecordLimits, spool: Option<SpoolWriter>) -> Self {
+        Self {
+            max_frames: limits
+                .memory_frames(clip.channels, clip.sample_rate)
+                .min(limits.duration_frames(clip.sample_rate)),
+            silence_level: 10f32.powf(limits.silence_threshold_db / 20.0),
+            silent_frames: 0,
+            clip,
+            spool,
+        }
+    }
+
+    /// Keeps everything in the ring so far, up to the limit
+    pub fn take(&mut self, rx: &mut Consumer<f32>) {
+        if let Ok(chunk) = rx.read_chunk(rx.slots()) {
+            // the ring holds whole frames and is a whole number of them long,
+            // so neither part ends in the middle of one
+            let (first, second) = chunk.as_slices();
+            self.keep(first);
+            self.keep(second);
+            chunk.commit_all();
+        }
+    }
+
+    /// Keeps a run of whole frames, as much of it as the limit leaves room for
+    pub fn keep(&mut self, chunk: &[f32]) {
+        let kept = self.clip.channels as usize;
+
+        let room = self.max_frames.saturating_sub(self.clip.frames());
+        let chunk = &chunk[..chunk.len().min(room.saturating_mul(kept))];
+
+        // grow by hand, doubling could ask for far more than the limit
+        let samples = &mut self.clip.samples;
+        let max_samples = self.max_frames.saturating_mul(kept);
+        if samples.capacity() - samples.len() < chunk.len() {
+            let grow = samples
+                .capacity()
+                .max(chunk.len())
+                .min(max_samples - samples.len());
+            samples.reserve_exact(grow);
+        }
+        samples.extend_from_slice(chunk);
+
+        for frame in chunk.chunks(kept) {
+            let loudest = frame.iter().fold(0f32, |loudest, s| loudest.max(s.abs()));
+            if loudest < self.silence_level {
+                self.silent_frames += 1;
+            } else {
+                self.silent_frames = 0;
+            }
+        }
+
+        self.spool(|spool| spool.write(chunk));
+    }
+
+    /// What has been kept so far
+    pub fn clip(&self) -> &AudioClip {
+        &self.clip
+    }
+
+    pub fn into_clip(self) -> AudioClip {
+        self.clip
+    }
+
+    /// The most frames the limits let the clip have
+    pub fn max_frames(&self) -> usize {
+        self.max_frames
+    }
+
+    /// Frames at the end of the clip that are all quieter than the silence threshold
+    pub fn silent_frames(&self) -> usize {
+        self.silent_frames
+    }
+
+    /// Where the recording is, as it will be saved
+    pub fn position_ms(&self) -> u64 {
+        self.clip.frames() as u64 * 1000 / self.clip.sample_rate.max(1) as u64
+    }
+
+    // A spool file that can't be written to is given up on, the clip is still kept in memory
+    pub(crate) fn spool(&mut self, write: impl FnOnce(&mut SpoolWriter) -> Result<()>) {
+        if let Some(spool) = &mut self.spool
+            && let Err(err) = write(spool)
+        {
+            // raw mode might be on
+            eprint!(
+                "Could not write to the spool file, carrying on without it: {}\r\n",
+                err
+            );
+            self.spool = None;
+        }
+    }
+}
+
+/// Cuts the last `silent_frames` frames off the clip.
+/// Markers made during them are moved to the new end
+pub fn cut_silence(clip: &mut AudioClip, silent_frames: usize, markers: &mut [Marker]) {
+    let kept_frames = clip.frames().saturating_sub(silent_frames);
+    clip.samples.truncate(kept_frames * clip.channels as usize);
+
+    let end_ms = kept_frames as u64 * 1000 / clip.sample_rate.max(1) as u64;
+    for marker in markers {
+        marker.position_ms = marker.position_ms.min(end_ms);
+    }
+}
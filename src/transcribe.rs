@@ -0,0 +1,103 @@
+#![allow(non_snake_case)]
+use crate::audio_clip::AudioClip;
+use crate::error::{Error, Result};
+use std::path::Path;
+
+/// Whisper models only take 16kHz mono audio
+pub const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+/// Environment variable pointing at the model file used when none is given
+pub const MODEL_ENV_VAR: &str = "OXYGEN_WHISPER_MODEL";
+
+/// One stretch of speech in a transcript
+#[derive(Debug, Clone)]
+pub struct Segment {
+    // milliseconds from the start of the clip
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+}
+
+/// What was said in a clip
+#[derive(Debug, Clone, Default)]
+pub struct Transcript {
+    pub segments: Vec<Segment>,
+}
+
+impl Transcript {
+    /// The whole transcript as plain text, without the timestamps
+    pub fn text(&self) -> String {
+        self.segments
+            .iter()
+            .map(|segment| segment.text.trim())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Runs a whisper.cpp compatible GGML model (`ggml-base.en.bin` and friends) over the clip.
+///
+/// Everything happens locally, the model file is the only thing needed.
+#[cfg(feature = "transcribe")]
+pub fn transcribe(clip: &AudioClip, model: &Path) -> Result<Transcript> {
+    use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+    if !model.exists() {
+        return Err(Error::Transcription(format!(
+            "Model file {} not found",
+            model.display()
+        )));
+    }
+
+    let whisper_error = |err: whisper_rs::WhisperError| Error::Transcription(err.to_string());
+
+    let samples = clip.to_mono().resample(WHISPER_SAMPLE_RATE).samples;
+
+    let context = WhisperContext::new_with_params(model, WhisperContextParameters::default())
+        .map_err(whisper_error)?;
+    let mut state = context.create_state().map_err(whisper_error)?;
+
+    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    params.set_language(Some("auto"));
+    params.set_n_threads(
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .try_into()?,
+    );
+    // whisper.cpp likes to print everything to stdout otherwise
+    params.set_print_special(false);
+    params.set_print_progress(false);
+    params.set_print_realtime(false);
+    params.set_print_timestamps(false);
+
+    state.full(params, &samples).map_err(whisper_error)?;
+
+    // whisper gives timestamps in centiseconds
+    let to_ms = |timestamp: i64| u64::try_from(timestamp * 10).unwrap_or(0);
+
+    let segments = state
+        .as_iter()
+        .map(|segment| {
+            Ok(Segment {
+                start_ms: to_ms(segment.start_timestamp()),
+                end_ms: to_ms(segment.end_timestamp()),
+                text: segment
+                    .to_str_lossy()
+                    .map_err(whisper_error)?
+                    .trim()
+                    .to_string(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Transcript { segments })
+}
+
+/// Without the `transcribe` feature there is no model to run
+#[cfg(not(feature = "transcribe"))]
+pub fn transcribe(_clip: &AudioClip, _model: &Path) -> Result<Transcript> {
+    Err(Error::Transcription(
+        "Oxygen was built without the `transcribe` feature".to_string(),
+    ))
+}
@@ -0,0 +1,133 @@
+#![allow(non_snake_case)]
+//! Writing clips out as wav files.
+
+use crate::dither;
+use crate::error::{Error, Result};
+use crate::metadata::Metadata;
+use std::io::Cursor;
+use std::path::Path;
+
+/// How a clip gets written as wav
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WavSettings {
+    // 16, 24 or 32
+    pub bits_per_sample: u8,
+    // float samples instead of integers, only at 32 bits
+    pub float: bool,
+    // TPDF dither when going down to 16 or 24 bit integers
+    pub dither: bool,
+}
+
+impl Default for WavSettings {
+    fn default() -> Self {
+        Self {
+            bits_per_sample: 32,
+            float: true,
+            dither: true,
+        }
+    }
+}
+
+impl WavSettings {
+    /// Checks the settings are something a wav file can hold
+    pub fn validate(&self) -> Result<()> {
+        if self.float && self.bits_per_sample != 32 {
+            return Err(Error::InvalidExportSettings(format!(
+                "float wav samples are 32 bits, got {}",
+                self.bits_per_sample
+            )));
+        }
+
+        if ![16, 24, 32].contains(&self.bits_per_sample) {
+            return Err(Error::InvalidExportSettings(format!(
+                "wav bit depth has to be 16, 24 or 32, got {}",
+                self.bits_per_sample
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+// Puts `chunk` in front of the data chunk of the wav header in `bytes`,
+// readers that stop at the data chunk would never see it after
+fn insert_before_data(bytes: &mut Vec<u8>, chunk: &[u8]) -> Result<()> {
+    // past `RIFF`, the size and `WAVE`
+    let mut offset = 12;
+
+    while offset + 8 <= bytes.len() && &bytes[offset..offset + 4] != b"data" {
+        let size = read_u32(bytes, offset + 4) as usize;
+        offset += 8 + size + size % 2;
+    }
+
+    let riff_size = read_u32(bytes, 4)
+        .checked_add(u32::try_from(chunk.len())?)
+        .ok_or_else(|| Error::Export("the metadata doesn't fit in a wav file".to_string()))?;
+
+    let offset = offset.min(bytes.len());
+    bytes.splice(offset..offset, chunk.iter().copied());
+    bytes[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+    Ok(())
+}
+
+/// Writes interleaved `samples` to `path` as a wav file, with `metadata` in a `LIST/INFO` chunk
+pub fn write(
+    path: impl AsRef<Path>,
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    settings: &WavSettings,
+    metadata: &Metadata,
+) -> Result<()> {
+    settings.validate()?;
+
+    let path = path.as_ref();
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: settings.bits_per_sample as u16,
+        sample_format: if settings.float {
+            hound::SampleFormat::Float
+        } else {
+            hound::SampleFormat::Int
+        },
+    };
+
+    // only the header is put together in memory, the samples go straight to the file
+    let mut header = Cursor::new(Vec::new());
+    hound::WavWriter::new(&mut header, spec)?.finalize()?;
+    let mut header = header.into_inner();
+    insert_before_data(&mut header, &metadata.riff_info_chunk())?;
+
+    // hound keeps the sizes in a u32 without checking, so make sure the whole file fits first
+    let data_size = samples.len() as u64 * (settings.bits_per_sample / 8) as u64;
+    if (header.len() - 8) as u64 + data_size > u32::MAX as u64 {
+        return Err(Error::Export(format!(
+            "{} samples don't fit in a wav file",
+            samples.len()
+        )));
+    }
+
+    std::fs::write(path, header)?;
+    let mut writer = hound::WavWriter::append(path)?;
+
+    if settings.float {
+        for sample in samples {
+            writer.write_sample(*sample)?;
+        }
+    } else {
+        let ints = dither::quantize(samples, settings.bits_per_sample as u32, settings.dither);
+        for sample in ints {
+            writer.write_sample(sample)?;
+        }
+    }
+
+    writer.finalize()?;
+
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use chrono::{TimeZone, Utc};
+use oxygen::{AudioClip, Error, RecordLimits, ResampleQuality};
+use std::time::Duration;
+
+// A clip where every sample says which frame it is, so cuts are easy to check
+fn counting(sample_rate: u32, channels: u16, frames: usize) -> AudioClip {
+    let samples = (0..frames)
+        .flat_map(|frame| (0..channels).map(move |channel| frame as f32 + channel as f32 / 10.0))
+        .collect();
+
+    AudioClip::new(
+        sample_rate,
+        channels,
+        samples,
+        Some(3),
+        "counting".to_string(),
+        Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap(),
+    )
+}
+
+fn constant(sample_rate: u32, channels: u16, frames: usize, value: f32, name: &str) -> AudioClip {
+    AudioClip::new(
+        sample_rate,
+        channels,
+        vec![value; frames * channels as usize],
+        None,
+        name.to_string(),
+        Utc::now(),
+    )
+}
+
+fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+}
+
+#[test]
+fn range_cuts_on_frames() {
+    let clip = counting(1000, 2, 3000);
+
+    let part = clip.range(ms(500), Some(ms(1250))).unwrap();
+    assert_eq!(part.channels, 2);
+    assert_eq!(part.frames(), 750);
+    assert_eq!(part.samples[0], 500.0);
+    assert_eq!(part.samples[1], 500.1);
+    assert_eq!(part.samples[part.samples.len() - 1], 1249.1);
+    assert_eq!(part.name, clip.name);
+    assert_eq!(part.date, clip.date);
+}
+
+#[test]
+fn range_runs_to_the_end() {
+    let clip = counting(1000, 1, 3000);
+
+    assert_eq!(clip.range(ms(2000), None).unwrap().frames(), 1000);
+    assert_eq!(clip.range(ms(2000), Some(ms(9000))).unwrap().frames(), 1000);
+    assert_eq!(
+        clip.range(Duration::ZERO, None).unwrap().samples,
+        clip.samples
+    );
+}
+
+#[test]
+fn range_has_to_be_inside_the_clip() {
+    let clip = counting(1000, 1, 3000);
+
+    for (from, to) in [
+        (ms(3000), None),
+        (ms(5000), Some(ms(6000))),
+        (ms(1000), Some(ms(1000))),
+        (ms(2000), Some(ms(1000))),
+    ] {
+        assert!(
+            matches!(clip.range(from, to), Err(Error::Export(_))),
+            "{:?} {:?}",
+            from,
+            to
+        );
+    }
+}
+
+#[test]
+fn concat_joins_with_gaps() {
+    let clips = [
+        constant(1000, 1, 100, 0.5, "first"),
+        constant(1000, 1, 200, -0.5, "second"),
+        constant(1000, 1, 50, 0.25, "third"),
+    ];
+
+    let joined = AudioClip::concat(&clips, ms(30), ResampleQuality::Normal).unwrap();
+    assert_eq!(joined.name, "first");
+    assert_eq!(joined.date, clips[0].date);
+    assert_eq!(joined.sample_rate, 1000);
+    assert_eq!(joined.frames(), 100 + 30 + 200 + 30 + 50);
+
+    assert!(joined.samples[..100].iter().all(|&s| s == 0.5));
+    assert!(joined.samples[100..130].iter().all(|&s| s == 0.0));
+    assert!(joined.samples[130..330].iter().all(|&s| s == -0.5));
+    assert!(joined.samples[330..360].iter().all(|&s| s == 0.0));
+    assert!(joined.samples[360..].iter().all(|&s| s == 0.25));
+}
+
+#[test]
+fn concat_brings_the_clips_together() {
+    let clips = [
+        constant(16000, 1, 16000, 0.5, "mono"),
+        constant(48000, 2, 48000, 0.25, "stereo"),
+    ];
+
+    let joined = AudioClip::concat(&clips, Duration::ZERO, ResampleQuality::Normal).unwrap();
+    assert_eq!(joined.sample_rate, 48000);
+    assert_eq!(joined.channels, 2);
+    assert_eq!(joined.frames(), 48000 * 2);
+
+    // the mono clip is on both channels, away from the edges where the filter sees silence
+    let middle = &joined.samples[2 * 20000..2 * 28000];
+    assert!(middle.iter().all(|s| (s - 0.5).abs() < 1e-3));
+    assert_eq!(joined.samples[2 * 48000..], clips[1].samples[..]);
+}
+
+#[test]
+fn concat_needs_a_clip() {
+    assert!(matches!(
+        AudioClip::concat(&[], ms(10), ResampleQuality::Normal),
+        Err(Error::Export(_))
+    ));
+}
+
+#[test]
+fn record_limits_have_to_leave_room() {
+    assert!(RecordLimits::default().validate().is_ok());
+
+    for limits in [
+        RecordLimits {
+            max_duration: Some(Duration::ZERO),
+            ..Default::default()
+        },
+        RecordLimits {
+            stop_on_silence: Some(Duration::ZERO),
+            ..Default::default()
+        },
+        RecordLimits {
+            silence_threshold_db: f32::NAN,
+            ..Default::default()
+        },
+        RecordLimits {
+            max_bytes: 0,
+            ..Default::default()
+        },
+    ] {
+        assert!(
+            matches!(limits.validate(), Err(Error::InvalidRecordLimits(_))),
+            "{:?}",
+            limits
+        );
+    }
+}
@@ -0,0 +1,59 @@
+//! Setup the integration tests share, each of them pulls it in with `mod common;`
+
+// every test file is a crate of its own and only uses some of this
+#![allow(dead_code)]
+
+use chrono::{TimeZone, Utc};
+use oxygen::{AudioClip, Db};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Where the journal of a test lives in its temporary directory
+pub fn db_path(dir: &tempfile::TempDir) -> PathBuf {
+    dir.path().join("oxygen.sqlite")
+}
+
+/// A new journal with nothing in it
+pub fn empty_db(dir: &tempfile::TempDir) -> Db {
+    Db::open_at(db_path(dir)).unwrap()
+}
+
+/// `frames` frames of mono silence at 48kHz, dated now
+pub fn silence(name: &str, frames: usize) -> AudioClip {
+    AudioClip::new(
+        48000,
+        1,
+        vec![0.0; frames],
+        None,
+        name.to_string(),
+        Utc::now(),
+    )
+}
+
+/// A journal with a second of silence for every name, a day apart in that order
+pub fn db_with_clips(dir: &tempfile::TempDir, names: &[&str]) -> Db {
+    let db = empty_db(dir);
+
+    for (day, name) in names.iter().enumerate() {
+        let mut clip = AudioClip {
+            date: Utc
+                .with_ymd_and_hms(2020, 1, day as u32 + 1, 0, 0, 0)
+                .unwrap(),
+            ..silence(name, 48000)
+        };
+        db.save(&mut clip).unwrap();
+    }
+
+    db
+}
+
+/// The binary, on the journal at `db` and without a config file
+pub fn oxygen(db: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_Oxygen"));
+    command
+        .arg("--db")
+        .arg(db)
+        .env_remove("OXYGEN_CONFIG")
+        .env_remove("OXYGEN_TRASH_DAYS");
+    command
+}
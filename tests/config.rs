@@ -0,0 +1,90 @@
+use oxygen::{Config, DeviceChoice, Error};
+
+#[test]
+fn missing_file_is_an_empty_config() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let config = Config::load(dir.path().join("config.toml")).unwrap();
+    assert_eq!(config, Config::default());
+}
+
+#[test]
+fn saves_and_loads() {
+    let dir = tempfile::tempdir().unwrap();
+    // the folder is made on the way
+    let path = dir.path().join("oxygen").join("config.toml");
+
+    let config = Config {
+        record: DeviceChoice {
+            host: Some("ALSA".to_string()),
+            device: Some("USB Microphone".to_string()),
+            rate: Some(48000),
+        },
+        play: DeviceChoice {
+            device: Some("2".to_string()),
+            ..Default::default()
+        },
+    };
+
+    config.save(&path).unwrap();
+    assert_eq!(Config::load(&path).unwrap(), config);
+
+    // nothing is written for what was left out
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(!text.contains("rate = 0"), "{}", text);
+    assert_eq!(text.matches("host").count(), 1, "{}", text);
+}
+
+#[test]
+fn reads_a_hand_written_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+
+    std::fs::write(&path, "[record]\ndevice = \"Mic\"\nrate = 44100\n").unwrap();
+
+    let config = Config::load(&path).unwrap();
+    assert_eq!(config.record.device.as_deref(), Some("Mic"));
+    assert_eq!(config.record.rate, Some(44100));
+    assert_eq!(config.play, DeviceChoice::default());
+}
+
+#[test]
+fn rejects_what_it_does_not_know() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+
+    for text in [
+        "[record]\ndevise = \"Mic\"\n",
+        "[record]\nrate = \"fast\"\n",
+        "[[",
+    ] {
+        std::fs::write(&path, text).unwrap();
+        assert!(
+            matches!(Config::load(&path), Err(Error::Config(..))),
+            "{}",
+            text
+        );
+    }
+}
+
+#[test]
+fn arguments_win_over_the_config() {
+    let saved = DeviceChoice {
+        host: Some("JACK".to_string()),
+        device: Some("saved".to_string()),
+        rate: Some(44100),
+    };
+    let given = DeviceChoice {
+        device: Some("given".to_string()),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        given.or(saved),
+        DeviceChoice {
+            host: Some("JACK".to_string()),
+            device: Some("given".to_string()),
+            rate: Some(44100),
+        }
+    );
+}
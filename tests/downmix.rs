@@ -0,0 +1,126 @@
+use chrono::Utc;
+use oxygen::{AudioClip, Downmix, Error};
+
+// `frames` frames where every channel holds its own index plus one, like [1, 2, 3, 1, 2, 3, ..]
+fn numbered(channels: u16, frames: usize) -> AudioClip {
+    AudioClip::new(
+        48000,
+        channels,
+        (0..frames)
+            .flat_map(|_| (1..=channels).map(|channel| channel as f32))
+            .collect(),
+        None,
+        "numbered".to_string(),
+        Utc::now(),
+    )
+}
+
+// A tone on the channel `loud`, and nothing on the others
+fn only_on(channels: u16, loud: u16, frames: usize) -> AudioClip {
+    AudioClip::new(
+        48000,
+        channels,
+        (0..frames)
+            .flat_map(|i| {
+                let sample = (i as f32 * 440.0 * std::f32::consts::TAU / 48000.0).sin() / 2.0;
+                (0..channels).map(move |channel| if channel == loud { sample } else { 0.0 })
+            })
+            .collect(),
+        None,
+        "tone".to_string(),
+        Utc::now(),
+    )
+}
+
+fn loudest(clip: &AudioClip) -> f32 {
+    clip.samples.iter().fold(0f32, |l, s| l.max(s.abs()))
+}
+
+#[test]
+fn each_choice_takes_its_channel() {
+    let clip = numbered(3, 100);
+
+    let downmixed = |downmix| {
+        let mono = clip.downmix(downmix).unwrap();
+        assert_eq!(mono.channels, 1);
+        assert_eq!(mono.samples.len(), 100);
+        mono.samples
+    };
+
+    assert_eq!(downmixed(Downmix::Average), vec![2.0; 100]);
+    assert_eq!(downmixed(Downmix::Left), vec![1.0; 100]);
+    assert_eq!(downmixed(Downmix::Right), vec![2.0; 100]);
+    assert_eq!(downmixed(Downmix::Channel(2)), vec![3.0; 100]);
+    assert_eq!(downmixed(Downmix::MaxEnergy), vec![3.0; 100]);
+}
+
+#[test]
+fn a_channel_past_the_last_is_an_error() {
+    let clip = numbered(2, 100);
+
+    assert!(matches!(
+        clip.downmix(Downmix::Channel(2)),
+        Err(Error::NoSuchChannel(2, 2))
+    ));
+    assert!(matches!(
+        numbered(3, 10).downmix(Downmix::Channel(7)),
+        Err(Error::NoSuchChannel(7, 3))
+    ));
+}
+
+#[test]
+fn mono_clips_are_left_alone() {
+    let clip = numbered(1, 100);
+
+    // even a channel that isn't there, there is nothing to choose between
+    for downmix in [Downmix::Average, Downmix::Right, Downmix::Channel(5)] {
+        assert_eq!(clip.downmix(downmix).unwrap().samples, clip.samples);
+    }
+    assert_eq!(clip.to_mono().samples, clip.samples);
+}
+
+#[test]
+fn one_sided_stereo_does_not_downmix_to_silence() {
+    let left = only_on(2, 0, 4800);
+    let right = only_on(2, 1, 4800);
+    let tone = loudest(&left);
+
+    assert!((loudest(&left.downmix(Downmix::MaxEnergy).unwrap()) - tone).abs() < 1e-6);
+    assert!((loudest(&right.downmix(Downmix::MaxEnergy).unwrap()) - tone).abs() < 1e-6);
+    assert!((loudest(&left.downmix(Downmix::Left).unwrap()) - tone).abs() < 1e-6);
+    assert!((loudest(&right.downmix(Downmix::Right).unwrap()) - tone).abs() < 1e-6);
+
+    // the quiet side really is quiet, and the average only halves the tone
+    assert_eq!(loudest(&left.downmix(Downmix::Right).unwrap()), 0.0);
+    assert!((loudest(&left.downmix(Downmix::Average).unwrap()) - tone / 2.0).abs() < 1e-6);
+}
+
+#[test]
+fn to_mono_averages_every_frame() {
+    let clip = AudioClip::new(
+        48000,
+        2,
+        vec![1.0, 0.0, 0.5, 0.5, -1.0, 1.0],
+        None,
+        "frames".to_string(),
+        Utc::now(),
+    );
+
+    let mono = clip.to_mono();
+    assert_eq!(mono.channels, 1);
+    assert_eq!(mono.sample_rate, 48000);
+    assert_eq!(mono.samples, [0.5, 0.5, 0.0]);
+}
+
+#[test]
+fn first_channels_drops_the_rest() {
+    let clip = numbered(3, 4);
+
+    let stereo = clip.first_channels(2);
+    assert_eq!(stereo.channels, 2);
+    assert_eq!(stereo.samples, [1.0, 2.0, 1.0, 2.0, 1.0, 2.0, 1.0, 2.0]);
+
+    // asking for as many or more than there are changes nothing
+    assert_eq!(clip.first_channels(3).samples, clip.samples);
+    assert_eq!(clip.first_channels(8).channels, 3);
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use audiopus::Application;
+use chrono::Utc;
+use oxygen::internal_encoding::{FRAME_DURATIONS_MS, HeaderV2};
+use oxygen::{AudioClip, BitrateMode, EncoderSettings, Error, Quality};
+
+fn tone(name: &str) -> AudioClip {
+    AudioClip::new(
+        48000,
+        1,
+        (0..48000).map(|i| (i as f32 / 10.0).sin() / 2.0).collect(),
+        None,
+        name.to_string(),
+        Utc::now(),
+    )
+}
+
+#[test]
+fn settings_are_stored_with_the_clip() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::empty_db(&dir);
+
+    let settings = EncoderSettings {
+        bitrate: 32000,
+        frame_ms: 40.0,
+        complexity: 3,
+        bitrate_mode: BitrateMode::Cbr,
+        application: Application::LowDelay,
+    };
+    db.save_with(&mut tone("clip"), &settings).unwrap();
+
+    let (meta, encoded) = db.encoded("clip").unwrap();
+    assert_eq!(meta.clip_encoder, settings);
+
+    // and the clip was really encoded with them
+    let header = HeaderV2::read(&encoded).unwrap();
+    assert_eq!(header.bitrate, 32000);
+    assert_eq!(header.frame_size, 48 * 40);
+    assert_eq!(header.application, Application::LowDelay);
+    assert_eq!(db.load("clip").unwrap().unwrap().samples.len(), 48000);
+}
+
+#[test]
+fn every_preset_and_frame_duration_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::empty_db(&dir);
+
+    for (i, quality) in [
+        Quality::Low,
+        Quality::Voice,
+        Quality::High,
+        Quality::Archival,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let name = format!("preset {}", i);
+        db.save_with(&mut tone(&name), &quality.settings()).unwrap();
+        assert_eq!(db.info(&name).unwrap().clip_encoder, quality.settings());
+        assert_eq!(db.load(&name).unwrap().unwrap().samples.len(), 48000);
+    }
+
+    for frame_ms in FRAME_DURATIONS_MS {
+        let name = format!("{} ms", frame_ms);
+        let settings = EncoderSettings {
+            frame_ms,
+            ..Default::default()
+        };
+        db.save_with(&mut tone(&name), &settings).unwrap();
+        assert_eq!(db.info(&name).unwrap().clip_encoder.frame_ms, frame_ms);
+        assert_eq!(db.load(&name).unwrap().unwrap().samples.len(), 48000);
+    }
+
+    // a higher bitrate takes more room
+    let size = |name: &str| db.info(name).unwrap().clip_size;
+    assert!(size("preset 0") < size("preset 3"));
+}
+
+#[test]
+fn rejects_what_opus_cannot_do() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::empty_db(&dir);
+
+    for settings in [
+        EncoderSettings {
+            bitrate: 1000,
+            ..Default::default()
+        },
+        EncoderSettings {
+            frame_ms: 30.0,
+            ..Default::default()
+        },
+        EncoderSettings {
+            complexity: 11,
+            ..Default::default()
+        },
+    ] {
+        assert!(
+            matches!(
+                db.save_with(&mut tone("clip"), &settings),
+                Err(Error::InvalidEncoderSettings(_))
+            ),
+            "{:?}",
+            settings
+        );
+    }
+
+    // nothing was saved on the way
+    assert!(!db.name_taken("clip").unwrap());
+}
@@ -0,0 +1,97 @@
+use chrono::Utc;
+use oxygen::internal_encoding::{EncoderSettings, HeaderV2, decode_v2, encode_v2, packets_v2};
+use oxygen::{AudioClip, Error};
+
+// Half a second of a tone, v2 encoded
+fn encoded() -> Vec<u8> {
+    let clip = AudioClip::new(
+        48000,
+        1,
+        (0..24000).map(|i| (i as f32 / 10.0).sin() / 2.0).collect(),
+        None,
+        "clip".to_string(),
+        Utc::now(),
+    );
+
+    encode_v2(&clip, &EncoderSettings::default()).unwrap().1
+}
+
+// Puts a correct checksum over the whole blob again, so only the packet checksums are left to notice
+fn reseal(bytes: &mut [u8]) {
+    let body = bytes.len() - 4;
+    let checksum = crc32fast::hash(&bytes[..body]);
+    bytes[body..].copy_from_slice(&checksum.to_be_bytes());
+}
+
+fn assert_corrupt(bytes: &[u8]) {
+    assert!(
+        matches!(packets_v2(bytes), Err(Error::Decode(_))),
+        "the packets were read"
+    );
+    assert!(
+        matches!(decode_v2(bytes), Err(Error::Decode(_))),
+        "the clip was decoded"
+    );
+}
+
+#[test]
+fn untouched_clips_decode() {
+    let bytes = encoded();
+
+    let packets = packets_v2(&bytes).unwrap();
+    assert_eq!(packets.header, HeaderV2::read(&bytes).unwrap());
+    assert_eq!(packets.packets.len(), 24000 / 960);
+    assert_eq!(decode_v2(&bytes).unwrap().len(), 24000);
+}
+
+#[test]
+fn a_flipped_byte_in_a_packet_is_caught() {
+    let mut bytes = encoded();
+    // the first byte of the first packet, past the header, its length and checksum
+    let at = 21 + 6;
+    bytes[at] ^= 0x40;
+    assert_corrupt(&bytes);
+
+    // and by the checksum of the packet itself, when the one over everything is fixed up
+    reseal(&mut bytes);
+    match packets_v2(&bytes) {
+        Err(Error::Decode(message)) => assert!(message.contains("packet 0"), "{}", message),
+        _ => panic!("the packet checksum didn't catch it"),
+    }
+    assert!(matches!(decode_v2(&bytes), Err(Error::Decode(_))));
+}
+
+#[test]
+fn a_flipped_byte_in_the_header_is_caught() {
+    let original = encoded();
+
+    // channels, sample rate, sample count, bitrate and frame size
+    for at in [5, 6, 12, 16, 19] {
+        let mut bytes = original.clone();
+        bytes[at] ^= 0x01;
+        assert_corrupt(&bytes);
+    }
+
+    // anything that isn't a v2 clip at all
+    let mut bytes = original.clone();
+    bytes[0] ^= 0x01;
+    assert_corrupt(&bytes);
+    let mut bytes = original;
+    bytes[4] = 3;
+    assert_corrupt(&bytes);
+}
+
+#[test]
+fn a_truncated_clip_is_caught() {
+    let bytes = encoded();
+
+    for len in [bytes.len() - 1, bytes.len() - 4, bytes.len() / 2, 22, 10, 0] {
+        assert_corrupt(&bytes[..len]);
+    }
+
+    // cut in the middle of a packet, even with a checksum that matches what is left
+    let mut cut = bytes[..bytes.len() / 2].to_vec();
+    cut.extend_from_slice(&[0; 4]);
+    reseal(&mut cut);
+    assert_corrupt(&cut);
+}
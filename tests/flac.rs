@@ -0,0 +1,334 @@
+use chrono::Utc;
+use oxygen::{AudioClip, Error, FlacSettings, Metadata};
+use std::f64::consts::PI;
+use std::fs::File;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+// Deterministic noise in -1..1, a plain LCG is plenty for this
+fn noise(seed: u64, len: usize) -> Vec<f32> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((state >> 40) as f64 / (1u64 << 23) as f64 - 1.0) as f32
+        })
+        .collect()
+}
+
+// Something like a recording: a couple of tones with a bit of noise on top
+fn signal(rate: u32, len: usize, seed: u64) -> Vec<f32> {
+    noise(seed, len)
+        .iter()
+        .enumerate()
+        .map(|(i, n)| {
+            let t = i as f64 / rate as f64;
+            let tones = 0.4 * (2.0 * PI * 220.0 * t).sin() + 0.2 * (2.0 * PI * 3150.0 * t).sin();
+            (tones + 0.01 * *n as f64) as f32
+        })
+        .collect()
+}
+
+fn interleave(left: &[f32], right: &[f32]) -> Vec<f32> {
+    left.iter().zip(right).flat_map(|(&l, &r)| [l, r]).collect()
+}
+
+// What the samples look like once stored with `bits` bits, which is what has to come back out
+fn quantize(samples: &[f32], bits: u8) -> Vec<f32> {
+    let scale = (1i64 << (bits - 1)) as f32;
+    samples
+        .iter()
+        .map(|s| (s * scale).round().clamp(-scale, scale - 1.0) / scale)
+        .collect()
+}
+
+// The default settings, minus the dither so the samples come back exactly
+const EXACT: FlacSettings = FlacSettings {
+    bits_per_sample: 16,
+    compression_level: 5,
+    dither: false,
+};
+
+fn clip(sample_rate: u32, channels: u16, samples: Vec<f32>) -> AudioClip {
+    AudioClip::new(
+        sample_rate,
+        channels,
+        samples,
+        None,
+        "flac".to_string(),
+        Utc::now(),
+    )
+}
+
+// Exports the clip as FLAC and imports it again
+fn round_trip(clip: &AudioClip, settings: &FlacSettings) -> (AudioClip, u64) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("clip.flac").to_str().unwrap().to_string();
+
+    clip.export_flac(&path, settings, &Metadata::default())
+        .unwrap();
+    let size = std::fs::metadata(&path).unwrap().len();
+
+    (AudioClip::import("flac".to_string(), path).unwrap(), size)
+}
+
+fn assert_round_trips(clip: &AudioClip, settings: &FlacSettings) -> u64 {
+    let (imported, size) = round_trip(clip, settings);
+
+    assert_eq!(imported.sample_rate, clip.sample_rate, "{:?}", settings);
+    assert_eq!(imported.channels, clip.channels, "{:?}", settings);
+    assert_eq!(imported.samples.len(), clip.samples.len(), "{:?}", settings);
+    assert!(
+        imported.samples == quantize(&clip.samples, settings.bits_per_sample),
+        "{:?}: samples differ",
+        settings
+    );
+
+    size
+}
+
+#[test]
+fn mono_round_trips_at_every_level() {
+    // not a multiple of any block size, so the last block is a short one
+    let clip = clip(44100, 1, signal(44100, 44100 + 123, 1));
+
+    for compression_level in 0..=8 {
+        for bits_per_sample in [8, 16, 24] {
+            assert_round_trips(
+                &clip,
+                &FlacSettings {
+                    bits_per_sample,
+                    compression_level,
+                    dither: false,
+                },
+            );
+        }
+    }
+}
+
+#[test]
+fn stereo_round_trips_at_every_level() {
+    let left = signal(48000, 30001, 2);
+    let right: Vec<f32> = left
+        .iter()
+        .zip(noise(3, left.len()))
+        .map(|(l, n)| 0.8 * l + 0.05 * n)
+        .collect();
+    let clip = clip(48000, 2, interleave(&left, &right));
+
+    for compression_level in 0..=8 {
+        for bits_per_sample in [8, 16, 24] {
+            assert_round_trips(
+                &clip,
+                &FlacSettings {
+                    bits_per_sample,
+                    compression_level,
+                    dither: false,
+                },
+            );
+        }
+    }
+}
+
+#[test]
+fn stereo_channels_stay_apart() {
+    // only one side has anything on it, and the other one is the opposite of it
+    for (left, right) in [
+        (signal(16000, 5000, 4), vec![0.0; 5000]),
+        (vec![0.0; 5000], signal(16000, 5000, 5)),
+        (
+            signal(16000, 5000, 6),
+            signal(16000, 5000, 6).iter().map(|s| -s).collect(),
+        ),
+    ] {
+        assert_round_trips(&clip(16000, 2, interleave(&left, &right)), &EXACT);
+    }
+}
+
+#[test]
+fn awkward_signals_round_trip() {
+    let settings = EXACT;
+
+    // a single sample, and fewer samples than the predictors need
+    assert_round_trips(&clip(44100, 1, vec![0.5]), &settings);
+    assert_round_trips(&clip(44100, 2, vec![0.5, -0.5, 0.25, 0.0]), &settings);
+    assert_round_trips(&clip(44100, 1, signal(44100, 13, 7)), &settings);
+
+    // silence and a constant
+    assert_round_trips(&clip(44100, 1, vec![0.0; 10000]), &settings);
+    assert_round_trips(&clip(44100, 2, vec![0.25; 10000]), &settings);
+
+    // white noise at full scale doesn't predict at all
+    assert_round_trips(&clip(44100, 1, noise(8, 10000)), &settings);
+
+    // samples outside -1..1 get clipped
+    let loud: Vec<f32> = signal(44100, 10000, 9).iter().map(|s| s * 3.0).collect();
+    assert_round_trips(&clip(44100, 1, loud), &settings);
+
+    // square waves jump from one end to the other
+    let square: Vec<f32> = (0..10000)
+        .map(|i| if (i / 50) % 2 == 0 { 0.99 } else { -0.99 })
+        .collect();
+    assert_round_trips(&clip(44100, 1, square), &settings);
+}
+
+#[test]
+fn long_clips_round_trip() {
+    // more frames than fit in a single byte of the frame number
+    for compression_level in [0, 8] {
+        assert_round_trips(
+            &clip(8000, 1, signal(8000, 1152 * 300 + 7, 10)),
+            &FlacSettings {
+                bits_per_sample: 16,
+                compression_level,
+                dither: false,
+            },
+        );
+    }
+}
+
+#[test]
+fn other_sample_rates_round_trip() {
+    for rate in [8000, 11025, 22050, 32000, 96000] {
+        assert_round_trips(&clip(rate, 1, signal(rate, 3000, 11)), &EXACT);
+    }
+}
+
+#[test]
+fn compresses() {
+    let clip = clip(
+        44100,
+        2,
+        interleave(&signal(44100, 44100, 12), &signal(44100, 44100, 13)),
+    );
+    let raw = clip.samples.len() as u64 * 2;
+
+    let fastest = assert_round_trips(
+        &clip,
+        &FlacSettings {
+            bits_per_sample: 16,
+            compression_level: 0,
+            dither: false,
+        },
+    );
+    let smallest = assert_round_trips(
+        &clip,
+        &FlacSettings {
+            bits_per_sample: 16,
+            compression_level: 8,
+            dither: false,
+        },
+    );
+
+    // the noise alone takes about 9.4 of the 16 bits
+    assert!(fastest < raw * 4 / 5, "{} of {}", fastest, raw);
+    assert!(smallest < fastest * 19 / 20, "{} vs {}", smallest, fastest);
+}
+
+#[test]
+fn md5_matches() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("clip.flac");
+
+    for bits_per_sample in [8, 16, 24] {
+        let clip = clip(
+            44100,
+            2,
+            interleave(&signal(44100, 9000, 14), &signal(44100, 9000, 15)),
+        );
+        clip.export_flac(
+            path.to_str().unwrap(),
+            &FlacSettings {
+                bits_per_sample,
+                compression_level: 5,
+                dither: true,
+            },
+            &Metadata::default(),
+        )
+        .unwrap();
+
+        let mss = MediaSourceStream::new(Box::new(File::open(&path).unwrap()), Default::default());
+        let mut format = symphonia::default::get_probe()
+            .format(
+                &Hint::new(),
+                mss,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .unwrap()
+            .format;
+        let mut decoder = symphonia::default::get_codecs()
+            .make(
+                &format.default_track().unwrap().codec_params,
+                &DecoderOptions { verify: true },
+            )
+            .unwrap();
+
+        while let Ok(packet) = format.next_packet() {
+            decoder.decode(&packet).unwrap();
+        }
+
+        assert_eq!(
+            decoder.finalize().verify_ok,
+            Some(true),
+            "{}",
+            bits_per_sample
+        );
+    }
+}
+
+#[test]
+fn dither_stays_within_a_step() {
+    let clip = clip(44100, 1, signal(44100, 10000, 16));
+
+    for bits_per_sample in [8, 16, 24] {
+        let (imported, _) = round_trip(
+            &clip,
+            &FlacSettings {
+                bits_per_sample,
+                compression_level: 5,
+                dither: true,
+            },
+        );
+        let step = 1.0 / (1i64 << (bits_per_sample - 1)) as f32;
+
+        let rounded = quantize(&clip.samples, bits_per_sample);
+        assert_ne!(imported.samples, rounded, "{}", bits_per_sample);
+        for (a, b) in imported.samples.iter().zip(&rounded) {
+            assert!((a - b).abs() <= step, "{} {} {}", bits_per_sample, a, b);
+        }
+    }
+}
+
+#[test]
+fn settings_are_checked() {
+    for (bits_per_sample, compression_level) in [(12, 5), (32, 5), (0, 5), (16, 9)] {
+        let settings = FlacSettings {
+            bits_per_sample,
+            compression_level,
+            dither: false,
+        };
+        assert!(matches!(
+            settings.validate(),
+            Err(Error::InvalidExportSettings(_))
+        ));
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("clip.flac");
+    let result = clip(44100, 1, vec![0.0; 100]).export_flac(
+        path.to_str().unwrap(),
+        &FlacSettings {
+            bits_per_sample: 20,
+            compression_level: 5,
+            dither: false,
+        },
+        &Metadata::default(),
+    );
+    assert!(matches!(result, Err(Error::InvalidExportSettings(_))));
+}
@@ -0,0 +1,88 @@
+mod common;
+
+// Two clips a day apart, the first with a tag and notes over more than one line
+fn journal(dir: &tempfile::TempDir) -> std::path::PathBuf {
+    let db = common::db_with_clips(dir, &["first", "second, with a comma"]);
+    db.add_tag("first", "work").unwrap();
+    db.set_notes("first", Some("line one\nline two")).unwrap();
+
+    common::db_path(dir)
+}
+
+// Runs the binary on the journal at `db` and gives back what it printed
+fn oxygen(db: &std::path::Path, args: &[&str]) -> String {
+    let output = common::oxygen(db).args(args).output().unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn json_drops_the_clip_prefix() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["a"]);
+
+    let json = serde_json::to_value(db.info("a").unwrap()).unwrap();
+    for field in [
+        "id",
+        "name",
+        "date",
+        "tags",
+        "notes",
+        "sample_rate",
+        "channels",
+        "sample_count",
+        "size",
+        "codec_version",
+        "encoder",
+    ] {
+        assert!(json.get(field).is_some(), "{} missing from {}", field, json);
+    }
+    assert!(json.get("clip_name").is_none());
+
+    // search results and trashed clips are the clip with a bit more
+    let json = serde_json::to_value(&db.search("a").unwrap()[0]).unwrap();
+    assert_eq!(json["name"], "a");
+    assert!(json.get("snippet").is_some() && json.get("rank").is_some());
+
+    db.delete("a").unwrap();
+    let json = serde_json::to_value(&db.trash().unwrap()[0]).unwrap();
+    assert_eq!(json["name"], "a");
+    assert!(json.get("deleted_at").is_some());
+}
+
+#[test]
+fn list_in_every_format() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = journal(&dir);
+
+    let json: serde_json::Value =
+        serde_json::from_str(&oxygen(&db, &["--format", "json", "list"])).unwrap();
+    let clips = json.as_array().unwrap();
+    assert_eq!(clips.len(), 2);
+    assert_eq!(clips[0]["name"], "first");
+    assert_eq!(clips[0]["tags"], serde_json::json!(["work"]));
+    assert_eq!(clips[0]["notes"], "line one\nline two");
+    assert_eq!(clips[0]["sample_count"], 48000);
+    assert_eq!(clips[1]["notes"], serde_json::Value::Null);
+
+    let ndjson = oxygen(&db, &["--format", "ndjson", "list"]);
+    let lines: Vec<serde_json::Value> = ndjson
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines, clips.as_slice());
+
+    let csv = oxygen(&db, &["--format", "csv", "list"]);
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+    assert_eq!(&reader.headers().unwrap()[1], "name");
+    let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    assert_eq!(rows.len(), 2);
+    // commas and newlines are quoted, not split on
+    assert_eq!(&rows[0][4], "line one\nline two");
+    assert_eq!(&rows[1][1], "second, with a comma");
+}
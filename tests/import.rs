@@ -0,0 +1,54 @@
+mod common;
+
+use oxygen::{EncoderSettings, Error, Metadata};
+
+#[test]
+fn saves_the_notes_and_tags_with_the_clip() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::empty_db(&dir);
+
+    let mut imported = common::silence("walk", 4800);
+    let metadata = Metadata {
+        tags: vec!["ideas".to_string(), "outside".to_string()],
+        notes: Some("by the river".to_string()),
+        ..Default::default()
+    };
+    db.save_with_metadata(&mut imported, &EncoderSettings::default(), &metadata)
+        .unwrap();
+    assert!(imported.id.is_some());
+
+    let meta = db.info("walk").unwrap();
+    assert_eq!(meta.clip_tags, ["ideas", "outside"]);
+    assert_eq!(meta.clip_notes.as_deref(), Some("by the river"));
+}
+
+#[test]
+fn a_bad_tag_saves_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::empty_db(&dir);
+
+    let mut imported = common::silence("walk", 4800);
+    let metadata = Metadata {
+        tags: vec!["ideas".to_string(), "a,b".to_string()],
+        notes: Some("by the river".to_string()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        db.save_with_metadata(&mut imported, &EncoderSettings::default(), &metadata),
+        Err(Error::InvalidTag(_))
+    ));
+
+    assert!(imported.id.is_none());
+    assert!(!db.name_taken("walk").unwrap());
+    // and the tag that was fine isn't left behind either
+    assert!(db.list(&["ideas".to_string()]).unwrap().is_empty());
+
+    // so the same file can be imported once its tags are fixed
+    let metadata = Metadata {
+        tags: vec!["ideas".to_string()],
+        ..metadata
+    };
+    db.save_with_metadata(&mut imported, &EncoderSettings::default(), &metadata)
+        .unwrap();
+    assert_eq!(db.info("walk").unwrap().clip_tags, ["ideas"]);
+}
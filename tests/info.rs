@@ -0,0 +1,52 @@
+mod common;
+
+use oxygen::internal_encoding::CODEC_VERSION;
+use oxygen::{AudioClip, Error};
+use std::time::Duration;
+
+#[test]
+fn info_has_the_duration_size_and_codec() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::empty_db(&dir);
+
+    db.save(&mut common::silence("clip", 72000)).unwrap();
+
+    let (meta, encoded) = db.encoded("clip").unwrap();
+    assert_eq!(meta.clip_name, "clip");
+    assert_eq!(meta.clip_sample_rate, 48000);
+    assert_eq!(meta.clip_sample_count, 72000);
+    assert_eq!(meta.duration(), Duration::from_millis(1500));
+    assert_eq!(meta.clip_size, encoded.len() as u64);
+    assert_eq!(meta.clip_codec_version, CODEC_VERSION);
+
+    // list gives the same
+    let listed = &db.list(&[]).unwrap()[0];
+    assert_eq!(listed.clip_sample_count, 72000);
+    assert_eq!(listed.clip_size, meta.clip_size);
+}
+
+#[test]
+fn info_is_the_rate_the_clip_is_stored_at() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::empty_db(&dir);
+
+    // opus can't do 44.1kHz, so it is stored at 48kHz
+    let mut clip = AudioClip {
+        sample_rate: 44100,
+        ..common::silence("clip", 44100)
+    };
+    db.save(&mut clip).unwrap();
+
+    let meta = db.info("clip").unwrap();
+    assert_eq!(meta.clip_sample_rate, 48000);
+    let duration = meta.duration().as_secs_f64();
+    assert!((duration - 1.0).abs() < 0.01, "{}", duration);
+}
+
+#[test]
+fn info_needs_a_clip() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::empty_db(&dir);
+
+    assert!(matches!(db.info("missing"), Err(Error::ClipNotFound(_))));
+}
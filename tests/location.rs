@@ -0,0 +1,92 @@
+use std::path::Path;
+use std::process::{Command, Output};
+
+// Runs `list` from `dir`, with the home directories inside it and `OXYGEN_DB` if there is one
+fn oxygen(dir: &Path, env_db: Option<&Path>, args: &[&str]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_Oxygen"));
+    command
+        .current_dir(dir)
+        .env("HOME", dir.join("home"))
+        .env("XDG_DATA_HOME", dir.join("data-home"))
+        .env("XDG_CONFIG_HOME", dir.join("config-home"))
+        .env_remove("OXYGEN_DB")
+        .env_remove("OXYGEN_CONFIG")
+        .env_remove("OXYGEN_TRASH_DAYS")
+        .args(args)
+        .arg("list");
+    if let Some(db) = env_db {
+        command.env("OXYGEN_DB", db);
+    }
+
+    let output = command.output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+fn default_db(dir: &Path) -> std::path::PathBuf {
+    dir.join("data-home").join("oxygen").join("oxygen.sqlite")
+}
+
+#[test]
+fn uses_the_data_directory_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+
+    oxygen(dir.path(), None, &[]);
+
+    assert!(default_db(dir.path()).exists());
+    assert!(!dir.path().join("data").exists());
+}
+
+#[test]
+fn the_environment_overrides_the_default() {
+    let dir = tempfile::tempdir().unwrap();
+    let env_db = dir.path().join("from-env.sqlite");
+
+    oxygen(dir.path(), Some(&env_db), &[]);
+
+    assert!(env_db.exists());
+    assert!(!default_db(dir.path()).exists());
+}
+
+#[test]
+fn the_flag_overrides_the_environment() {
+    let dir = tempfile::tempdir().unwrap();
+    let env_db = dir.path().join("from-env.sqlite");
+    let flag_db = dir.path().join("from-flag.sqlite");
+
+    oxygen(
+        dir.path(),
+        Some(&env_db),
+        &["--db", flag_db.to_str().unwrap()],
+    );
+
+    assert!(flag_db.exists());
+    assert!(!env_db.exists());
+    assert!(!default_db(dir.path()).exists());
+}
+
+#[test]
+fn points_to_a_journal_left_at_the_old_location() {
+    let dir = tempfile::tempdir().unwrap();
+    let notice = "Found a journal at";
+    let stderr = |output: Output| String::from_utf8(output.stderr).unwrap();
+
+    // nothing at the old location, nothing to say
+    assert!(!stderr(oxygen(dir.path(), None, &[])).contains(notice));
+
+    // the old file and no new one
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("data")).unwrap();
+    std::fs::write(dir.path().join("data").join("oxygen.sqlite"), b"").unwrap();
+    let said = stderr(oxygen(dir.path(), None, &[]));
+    assert!(said.contains(notice), "{}", said);
+    assert!(said.contains("data/oxygen.sqlite"), "{}", said);
+
+    // now that the new one is there too it is in use, so the old one isn't brought up again
+    assert!(default_db(dir.path()).exists());
+    assert!(!stderr(oxygen(dir.path(), None, &[])).contains(notice));
+}
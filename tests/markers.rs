@@ -0,0 +1,42 @@
+mod common;
+
+use oxygen::{Error, Marker};
+
+fn markers(positions: &[u64]) -> Vec<Marker> {
+    positions
+        .iter()
+        .map(|&position_ms| Marker { position_ms })
+        .collect()
+}
+
+#[test]
+fn markers_are_kept_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["clip"]);
+
+    assert_eq!(db.markers("clip").unwrap(), []);
+
+    // made in this order, even if that is not the order they are in the clip
+    db.save_markers("clip", &markers(&[900, 100, 500])).unwrap();
+    assert_eq!(db.markers("clip").unwrap(), markers(&[900, 100, 500]));
+
+    db.save_markers("clip", &markers(&[250])).unwrap();
+    assert_eq!(db.markers("clip").unwrap(), markers(&[250]));
+}
+
+#[test]
+fn markers_follow_the_clip() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["clip"]);
+
+    db.save_markers("clip", &markers(&[100, 200])).unwrap();
+    db.rename("clip", "renamed").unwrap();
+    assert_eq!(db.markers("renamed").unwrap(), markers(&[100, 200]));
+
+    db.delete("renamed").unwrap();
+    assert!(matches!(db.markers("renamed"), Err(Error::ClipNotFound(_))));
+
+    db.empty_trash().unwrap();
+    db.save(&mut common::silence("renamed", 480)).unwrap();
+    assert_eq!(db.markers("renamed").unwrap(), []);
+}
@@ -0,0 +1,151 @@
+use chrono::{DateTime, TimeZone, Utc};
+use oxygen::{AudioClip, FlacSettings, Metadata, WavSettings};
+use symphonia::core::meta::{StandardTagKey, Tag, Value};
+
+fn clip() -> AudioClip {
+    let samples = (0..4000).map(|i| (i as f32 * 0.01).sin() * 0.5).collect();
+    AudioClip::new(
+        16000,
+        1,
+        samples,
+        None,
+        "clip".to_string(),
+        Utc.with_ymd_and_hms(2001, 2, 3, 4, 5, 6).unwrap(),
+    )
+}
+
+// Everything a clip can carry, with a couple of awkward characters
+fn walk() -> Metadata {
+    Metadata {
+        name: Some("Morning walk ☀".to_string()),
+        date: Some(Utc.with_ymd_and_hms(2024, 5, 1, 7, 30, 15).unwrap()),
+        tags: vec!["outside".to_string(), "with spaces".to_string()],
+        // odd length, RIFF pads the chunk after it
+        notes: Some("Birds, mostly.\nSome traffic".to_string()),
+    }
+}
+
+// Exports the clip with `export` to a file named `file` and imports it again
+fn round_trip(file: &str, export: impl Fn(&AudioClip, &str)) -> (AudioClip, Metadata) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(file).to_str().unwrap().to_string();
+
+    export(&clip(), &path);
+    AudioClip::import_with_metadata("imported".to_string(), path).unwrap()
+}
+
+#[test]
+fn wav_keeps_the_metadata() {
+    let (imported, metadata) = round_trip("clip.wav", |clip, path| {
+        clip.export_wav(path, &WavSettings::default(), &walk())
+            .unwrap()
+    });
+
+    assert_eq!(metadata, walk());
+    assert_eq!(imported.date, walk().date.unwrap());
+    assert_eq!(imported.name, "imported");
+    assert_eq!(imported.samples, clip().samples);
+}
+
+#[test]
+fn integer_wav_keeps_the_metadata() {
+    let settings = WavSettings {
+        bits_per_sample: 16,
+        float: false,
+        dither: true,
+    };
+    let (imported, metadata) = round_trip("clip.wav", |clip, path| {
+        clip.export_wav(path, &settings, &walk()).unwrap()
+    });
+
+    assert_eq!(metadata, walk());
+    assert_eq!(imported.samples.len(), clip().samples.len());
+}
+
+#[test]
+fn flac_keeps_the_metadata() {
+    let (imported, metadata) = round_trip("clip.flac", |clip, path| {
+        clip.export_flac(path, &FlacSettings::default(), &walk())
+            .unwrap()
+    });
+
+    assert_eq!(metadata, walk());
+    assert_eq!(imported.date, walk().date.unwrap());
+    assert_eq!(imported.samples.len(), clip().samples.len());
+}
+
+#[test]
+fn export_uses_the_clip_name_and_date() {
+    for file in ["clip.wav", "clip.flac"] {
+        let (imported, metadata) = round_trip(file, |clip, path| clip.export(path).unwrap());
+
+        assert_eq!(metadata.name.as_deref(), Some("clip"), "{}", file);
+        assert_eq!(metadata.date, Some(clip().date), "{}", file);
+        assert_eq!(metadata.tags, Vec::<String>::new(), "{}", file);
+        assert_eq!(metadata.notes, None, "{}", file);
+        assert_eq!(imported.date, clip().date, "{}", file);
+    }
+}
+
+#[test]
+fn files_without_metadata_import_as_before() {
+    for file in ["clip.wav", "clip.flac"] {
+        let (imported, metadata) = round_trip(file, |clip, path| {
+            if path.ends_with(".wav") {
+                clip.export_wav(path, &WavSettings::default(), &Metadata::default())
+                    .unwrap()
+            } else {
+                clip.export_flac(path, &FlacSettings::default(), &Metadata::default())
+                    .unwrap()
+            }
+        });
+
+        assert_eq!(metadata, Metadata::default(), "{}", file);
+        // the date the file was made
+        assert!(imported.date > clip().date, "{}", file);
+    }
+}
+
+#[test]
+fn tags_from_other_tools() {
+    let tags = [
+        Tag::new(
+            Some(StandardTagKey::TrackTitle),
+            "INAM",
+            Value::from("Title\0"),
+        ),
+        Tag::new(
+            Some(StandardTagKey::Date),
+            "DATE",
+            Value::from("2019-12-24"),
+        ),
+        Tag::new(None, "keywords", Value::from("one, two,,three")),
+        Tag::new(Some(StandardTagKey::Comment), "COMMENT", Value::from("  ")),
+        Tag::new(
+            Some(StandardTagKey::Artist),
+            "ARTIST",
+            Value::from("Someone"),
+        ),
+    ];
+
+    let metadata = Metadata::from_tags(&tags);
+    assert_eq!(metadata.name.as_deref(), Some("Title"));
+    assert_eq!(
+        metadata.date,
+        Some(
+            DateTime::parse_from_rfc3339("2019-12-24T00:00:00Z")
+                .unwrap()
+                .into()
+        )
+    );
+    assert_eq!(metadata.tags, ["one", "two", "three"]);
+    assert_eq!(metadata.notes, None);
+
+    // a date nothing can make sense of is left out
+    let tags = [Tag::new(
+        Some(StandardTagKey::Date),
+        "DATE",
+        Value::from("last tuesday"),
+    )];
+    assert_eq!(Metadata::from_tags(&tags).date, None);
+}
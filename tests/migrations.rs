@@ -0,0 +1,190 @@
+mod common;
+
+use chrono::{TimeZone, Utc};
+use oxygen::Db;
+use oxygen::internal_encoding::encode_v0;
+use rusqlite::{Connection, params};
+
+// A journal as the first version of Oxygen left it, raw samples and all
+fn first_version_journal(dir: &tempfile::TempDir, clips: &[(&str, usize)]) -> std::path::PathBuf {
+    let path = common::db_path(dir);
+    let connection = Connection::open(&path).unwrap();
+
+    connection
+        .execute_batch(
+            "
+            CREATE TABLE clips
+            (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                date TEXT NOT NULL,
+                sample_rate INTEGER NOT NULL,
+                samples BLOB NOT NULL
+            );
+            PRAGMA user_version = 1;
+            ",
+        )
+        .unwrap();
+
+    for (day, (name, frames)) in clips.iter().enumerate() {
+        let samples: Vec<f32> = (0..*frames)
+            .map(|i| (i as f32 / 20.0).sin() / 2.0)
+            .collect();
+
+        connection
+            .execute(
+                "INSERT INTO clips (name, date, sample_rate, samples) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    name,
+                    Utc.with_ymd_and_hms(2020, 1, day as u32 + 1, 0, 0, 0)
+                        .unwrap()
+                        .to_string(),
+                    48000,
+                    encode_v0(&samples)
+                ],
+            )
+            .unwrap();
+    }
+
+    path
+}
+
+#[test]
+fn old_clips_load_and_can_be_tagged() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = first_version_journal(&dir, &[("first", 48000), ("second", 9600)]);
+
+    let db = Db::open_at(&path).unwrap();
+
+    let clip = db.load("first").unwrap().unwrap();
+    assert_eq!(clip.sample_rate, 48000);
+    // opus works in whole packets, so a little padding is fine
+    assert!(clip.samples.len() >= 48000, "{}", clip.samples.len());
+
+    db.add_tag("second", "old").unwrap();
+    let tagged: Vec<_> = db
+        .list(&["old".to_string()])
+        .unwrap()
+        .into_iter()
+        .map(|meta| meta.clip_name)
+        .collect();
+    assert_eq!(tagged, ["second"]);
+
+    // and opening it again doesn't migrate it twice
+    drop(db);
+    let db = Db::open_at(&path).unwrap();
+    assert_eq!(db.list(&[]).unwrap().len(), 2);
+}
+
+#[test]
+fn old_clips_are_in_the_search_index() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = first_version_journal(&dir, &[("standup", 4800), ("walk", 4800)]);
+
+    let db = Db::open_at(&path).unwrap();
+
+    let found: Vec<_> = db
+        .search("standup")
+        .unwrap()
+        .into_iter()
+        .map(|result| result.meta.clip_name)
+        .collect();
+    assert_eq!(found, ["standup"]);
+}
+
+#[test]
+fn old_clips_are_not_in_the_trash() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = first_version_journal(&dir, &[("first", 4800)]);
+
+    let db = Db::open_at(&path).unwrap();
+
+    assert!(db.trash().unwrap().is_empty());
+    assert!(!db.in_trash("first").unwrap());
+    db.delete("first").unwrap();
+    assert!(db.in_trash("first").unwrap());
+}
+
+#[test]
+fn old_clips_get_their_duration_and_size() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = first_version_journal(&dir, &[("first", 96000)]);
+
+    let db = Db::open_at(&path).unwrap();
+
+    let (meta, encoded) = db.encoded("first").unwrap();
+    // still the format they were converted to on the way
+    assert_eq!(meta.clip_codec_version, 1);
+    assert_eq!(meta.clip_size, encoded.len() as u64);
+    assert_eq!(meta.clip_sample_rate, 48000);
+    // v1 clips are padded to whole packets
+    assert!(
+        meta.duration() >= std::time::Duration::from_secs(2),
+        "{:?}",
+        meta.duration()
+    );
+    assert!(meta.duration() < std::time::Duration::from_millis(2100));
+}
+
+#[test]
+fn old_clips_keep_the_settings_they_were_encoded_with() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = first_version_journal(&dir, &[("first", 4800)]);
+
+    let db = Db::open_at(&path).unwrap();
+
+    // v1 was always 24kbps, 20ms, complexity 9, vbr and audio
+    assert_eq!(
+        db.info("first").unwrap().clip_encoder,
+        oxygen::EncoderSettings::default()
+    );
+}
+
+#[test]
+fn old_clips_are_mono() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = first_version_journal(&dir, &[("first", 4800)]);
+
+    let db = Db::open_at(&path).unwrap();
+
+    assert_eq!(db.info("first").unwrap().clip_channels, 1);
+    assert_eq!(db.load("first").unwrap().unwrap().channels, 1);
+}
+
+#[test]
+fn a_step_that_fails_is_tried_again_next_time() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = first_version_journal(&dir, &[("first", 4800)]);
+
+    // something in the way of the search index, so building it (v5) fails halfway
+    Connection::open(&path)
+        .unwrap()
+        .execute("CREATE TABLE clips_fts (other TEXT)", [])
+        .unwrap();
+    assert!(Db::open_at(&path).is_err());
+
+    // the steps before it stuck, that one and the ones after it didn't
+    let connection = Connection::open(&path).unwrap();
+    let version: u32 = connection
+        .query_row("SELECT user_version FROM pragma_user_version", [], |r| {
+            r.get(0)
+        })
+        .unwrap();
+    assert_eq!(version, 4);
+    let triggers: u32 = connection
+        .query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'trigger'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(triggers, 0);
+
+    // once it is out of the way the rest is done on the next open
+    connection.execute("DROP TABLE clips_fts", []).unwrap();
+    drop(connection);
+
+    let db = Db::open_at(&path).unwrap();
+    assert_eq!(db.search("first").unwrap().len(), 1);
+    assert!(db.markers("first").unwrap().is_empty());
+}
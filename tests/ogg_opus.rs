@@ -0,0 +1,259 @@
+use audiopus::coder::Encoder;
+use audiopus::{Application, Channels, SampleRate};
+use chrono::{TimeZone, Utc};
+use oxygen::internal_encoding::{EncoderSettings, encode_v2, packets_v2};
+use oxygen::{AudioClip, Metadata, ogg_opus};
+
+struct Page {
+    header_type: u8,
+    granule: u64,
+    serial: u32,
+    sequence: u32,
+    // the segments of the page, packets ending on it end with a segment shorter than 255
+    segments: Vec<Vec<u8>>,
+}
+
+// The CRC Ogg uses: polynomial 0x04c11db7, not reflected, starting from zero
+fn ogg_crc(bytes: &[u8]) -> u32 {
+    let mut crc = 0u32;
+    for byte in bytes {
+        crc ^= (*byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+// Splits a file into its pages, checking the CRC of every one on the way
+fn pages(mut bytes: &[u8]) -> Vec<Page> {
+    let mut pages = Vec::new();
+
+    while !bytes.is_empty() {
+        assert_eq!(&bytes[..4], b"OggS");
+        assert_eq!(bytes[4], 0, "stream structure version");
+
+        let count = bytes[26] as usize;
+        let lacing = &bytes[27..27 + count];
+        let len = 27 + count + lacing.iter().map(|&l| l as usize).sum::<usize>();
+
+        let mut page = bytes[..len].to_vec();
+        let crc = u32::from_le_bytes(page[22..26].try_into().unwrap());
+        page[22..26].fill(0);
+        assert_eq!(ogg_crc(&page), crc, "CRC of page {}", pages.len());
+
+        let mut body = &bytes[27 + count..len];
+        let segments = lacing
+            .iter()
+            .map(|&l| {
+                let (segment, rest) = body.split_at(l as usize);
+                body = rest;
+                segment.to_vec()
+            })
+            .collect();
+
+        pages.push(Page {
+            header_type: bytes[5],
+            granule: u64::from_le_bytes(bytes[6..14].try_into().unwrap()),
+            serial: u32::from_le_bytes(bytes[14..18].try_into().unwrap()),
+            sequence: u32::from_le_bytes(bytes[18..22].try_into().unwrap()),
+            segments,
+        });
+        bytes = &bytes[len..];
+    }
+
+    pages
+}
+
+// The packets that end on each page, put back together from their segments
+fn packets(pages: &[Page]) -> Vec<Vec<Vec<u8>>> {
+    let mut packet = Vec::new();
+    pages
+        .iter()
+        .map(|page| {
+            let mut ended = Vec::new();
+            for segment in &page.segments {
+                packet.extend_from_slice(segment);
+                if segment.len() < 255 {
+                    ended.push(std::mem::take(&mut packet));
+                }
+            }
+            ended
+        })
+        .collect()
+}
+
+fn le_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+// The comments of an OpusTags packet, after checking the vendor is there
+fn comments(tags: &[u8]) -> Vec<String> {
+    assert_eq!(&tags[..8], b"OpusTags");
+    let vendor_len = le_u32(tags, 8) as usize;
+    assert!(
+        std::str::from_utf8(&tags[12..12 + vendor_len])
+            .unwrap()
+            .starts_with("Oxygen ")
+    );
+
+    let mut at = 12 + vendor_len;
+    let count = le_u32(tags, at);
+    at += 4;
+    let comments = (0..count)
+        .map(|_| {
+            let len = le_u32(tags, at) as usize;
+            let comment = String::from_utf8(tags[at + 4..at + 4 + len].to_vec()).unwrap();
+            at += 4 + len;
+            comment
+        })
+        .collect();
+    assert_eq!(at, tags.len());
+
+    comments
+}
+
+fn tone(sample_rate: u32, channels: u16, frames: usize) -> AudioClip {
+    AudioClip::new(
+        sample_rate,
+        channels,
+        (0..frames)
+            .flat_map(|i| {
+                let sample = (i as f32 * 440.0 * std::f32::consts::TAU / sample_rate as f32).sin();
+                (0..channels).map(move |_| sample / 2.0)
+            })
+            .collect(),
+        None,
+        "clip".to_string(),
+        Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap(),
+    )
+}
+
+// Writes the clip out and returns its pages and the sample count stored with it
+fn written(clip: &AudioClip, metadata: &Metadata) -> (Vec<Page>, usize, u32) {
+    let (_, encoded) = encode_v2(clip, &EncoderSettings::default()).unwrap();
+    let stored = packets_v2(&encoded).unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("clip.opus");
+    ogg_opus::write(&path, &stored, 7, metadata).unwrap();
+
+    (
+        pages(&std::fs::read(&path).unwrap()),
+        stored.packets.len(),
+        stored.header.sample_count,
+    )
+}
+
+fn lookahead(sample_rate: SampleRate, channels: Channels) -> u64 {
+    Encoder::new(sample_rate, channels, Application::Audio)
+        .unwrap()
+        .lookahead()
+        .unwrap() as u64
+}
+
+#[test]
+fn writes_the_headers_and_packets_in_pages() {
+    let clip = tone(48000, 1, 3 * 48000 - 500);
+    let metadata = Metadata {
+        tags: vec!["work".to_string(), "idea".to_string()],
+        notes: Some("notes".to_string()),
+        ..Metadata::from(&clip)
+    };
+    let (pages, packet_count, sample_count) = written(&clip, &metadata);
+    let packets = packets(&pages);
+
+    // one stream, numbered from the start, beginning and ending where it should
+    assert!(pages.iter().all(|page| page.serial == 7));
+    assert!(
+        pages
+            .iter()
+            .enumerate()
+            .all(|(i, page)| page.sequence == i as u32)
+    );
+    assert_eq!(pages[0].header_type, 0x02);
+    assert!(pages[1..].iter().all(|page| page.header_type & 0x02 == 0));
+    assert_eq!(pages.last().unwrap().header_type & 0x04, 0x04);
+
+    // the headers get a page of their own each
+    assert_eq!(packets[0].len(), 1);
+    assert_eq!(packets[1].len(), 1);
+    assert_eq!(pages[0].granule, 0);
+    assert_eq!(pages[1].granule, 0);
+
+    let head = &packets[0][0];
+    assert_eq!(head.len(), 19);
+    assert_eq!(&head[..8], b"OpusHead");
+    assert_eq!(head[8], 1, "version");
+    assert_eq!(head[9], 1, "channels");
+    let pre_skip = u16::from_le_bytes([head[10], head[11]]) as u64;
+    assert_eq!(pre_skip, lookahead(SampleRate::Hz48000, Channels::Mono));
+    assert_eq!(le_u32(head, 12), 48000, "input sample rate");
+    assert_eq!(&head[16..18], &[0, 0], "output gain");
+    assert_eq!(head[18], 0, "channel mapping family");
+
+    assert_eq!(
+        comments(&packets[1][0]),
+        [
+            "TITLE=clip",
+            "DATE=2020-01-02T03:04:05Z",
+            "KEYWORDS=work",
+            "KEYWORDS=idea",
+            "COMMENT=notes",
+        ]
+    );
+
+    // every stored packet is there, and pages of audio are about a second each
+    let audio: Vec<_> = packets[2..].iter().flatten().collect();
+    assert_eq!(audio.len(), packet_count);
+    assert!(pages.len() >= 2 + 3, "{}", pages.len());
+
+    // the granule counts up a frame a packet, and the last one stops where the clip does
+    let mut decoded = 0;
+    for (page, ended) in pages[2..].iter().zip(&packets[2..]).rev().skip(1).rev() {
+        decoded += ended.len() as u64 * 960;
+        assert_eq!(page.granule, decoded);
+    }
+    assert_eq!(
+        pages.last().unwrap().granule,
+        pre_skip + sample_count as u64
+    );
+}
+
+#[test]
+fn granules_are_counted_at_48khz() {
+    let clip = tone(24000, 2, 24000 - 300);
+    let (pages, _, sample_count) = written(&clip, &Metadata::default());
+    let packets = packets(&pages);
+
+    let head = &packets[0][0];
+    assert_eq!(head[9], 2, "channels");
+    assert_eq!(le_u32(head, 12), 24000, "input sample rate");
+    // the delay of a 24kHz encoder, counted in 48kHz samples
+    let pre_skip = u16::from_le_bytes([head[10], head[11]]) as u64;
+    assert_eq!(
+        pre_skip,
+        2 * lookahead(SampleRate::Hz24000, Channels::Stereo)
+    );
+
+    assert_eq!(
+        pages.last().unwrap().granule,
+        pre_skip + 2 * sample_count as u64
+    );
+
+    // nothing in the comments but the vendor
+    assert!(comments(&packets[1][0]).is_empty());
+}
+
+#[test]
+fn a_clip_without_audio_is_just_the_headers() {
+    let (pages, packet_count, _) = written(&tone(48000, 1, 0), &Metadata::default());
+
+    assert_eq!(packet_count, 0);
+    assert_eq!(pages.len(), 2);
+    assert_eq!(pages[1].header_type & 0x04, 0x04);
+}
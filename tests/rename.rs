@@ -0,0 +1,54 @@
+mod common;
+
+use oxygen::{Error, Marker};
+
+#[test]
+fn keeps_the_id_and_everything_attached() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["old"]);
+
+    db.add_tag("old", "work").unwrap();
+    db.set_notes("old", Some("notes")).unwrap();
+    db.save_markers("old", &[Marker { position_ms: 50 }])
+        .unwrap();
+    let id = db.info("old").unwrap().clip_id;
+
+    db.rename("old", "new").unwrap();
+
+    assert!(!db.name_taken("old").unwrap());
+    let meta = db.info("new").unwrap();
+    assert_eq!(meta.clip_id, id);
+    assert_eq!(meta.clip_tags, ["work"]);
+    assert_eq!(db.notes("new").unwrap().as_deref(), Some("notes"));
+    assert_eq!(db.markers("new").unwrap(), [Marker { position_ms: 50 }]);
+    assert_eq!(db.load("new").unwrap().unwrap().id, Some(id));
+}
+
+#[test]
+fn renaming_to_the_same_name_does_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["same"]);
+
+    db.rename("same", "same").unwrap();
+    assert!(db.info("same").is_ok());
+}
+
+#[test]
+fn needs_a_clip_and_a_free_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["a", "b"]);
+
+    assert!(matches!(db.rename("a", "b"), Err(Error::NameTaken(_))));
+    assert!(matches!(
+        db.rename("missing", "c"),
+        Err(Error::ClipNotFound(_))
+    ));
+    assert!(matches!(
+        db.rename("missing", "missing"),
+        Err(Error::ClipNotFound(_))
+    ));
+
+    // nothing changed
+    assert!(db.info("a").is_ok());
+    assert!(db.info("b").is_ok());
+}
@@ -0,0 +1,202 @@
+use oxygen::ResampleQuality;
+use oxygen::resample::{Resampler, resample};
+use std::f64::consts::PI;
+
+const QUALITIES: [ResampleQuality; 3] = [
+    ResampleQuality::Fast,
+    ResampleQuality::Normal,
+    ResampleQuality::Best,
+];
+
+// A linear sine sweep from `f0` to `f1` Hz over `seconds`, sampled at `rate`
+fn sweep(rate: u32, f0: f64, f1: f64, seconds: f64) -> Vec<f32> {
+    let frames = (rate as f64 * seconds) as usize;
+
+    (0..frames)
+        .map(|i| sweep_at(i as f64 / rate as f64, f0, f1, seconds))
+        .collect()
+}
+
+fn sweep_at(t: f64, f0: f64, f1: f64, seconds: f64) -> f32 {
+    let phase = 2.0 * PI * (f0 * t + (f1 - f0) * t * t / (2.0 * seconds));
+    (0.5 * phase.sin()) as f32
+}
+
+fn rms(samples: impl Iterator<Item = f32>) -> f64 {
+    let (sum, count) = samples.fold((0.0, 0), |(sum, count), s| {
+        (sum + (s as f64).powi(2), count + 1)
+    });
+    (sum / count as f64).sqrt()
+}
+
+fn db(ratio: f64) -> f64 {
+    20.0 * ratio.log10()
+}
+
+// Error of resampling a sweep below `f1` Hz, compared to the sweep sampled at the new rate,
+// in dB relative to the signal. The ends are skipped since the filter sees silence there
+fn passband_error(from: u32, to: u32, f1: f64, quality: ResampleQuality) -> f64 {
+    let seconds = 1.0;
+    let input = sweep(from, 20.0, f1, seconds);
+    let output = resample(&input, 1, from, to, quality);
+    let skip = to as usize / 20;
+
+    let expected: Vec<f32> = (0..output.len())
+        .map(|i| sweep_at(i as f64 / to as f64, 20.0, f1, seconds))
+        .collect();
+
+    let error = rms(output[skip..output.len() - skip]
+        .iter()
+        .zip(&expected[skip..output.len() - skip])
+        .map(|(a, b)| a - b));
+    let signal = rms(expected[skip..output.len() - skip].iter().copied());
+
+    db(error / signal)
+}
+
+// Level of whatever is left after downsampling a sweep that is entirely above the new nyquist,
+// in dB relative to the input
+fn aliasing(from: u32, to: u32, quality: ResampleQuality) -> f64 {
+    let input = sweep(
+        from,
+        to as f64 / 2.0 + 300.0,
+        from as f64 / 2.0 - 300.0,
+        1.0,
+    );
+    let output = resample(&input, 1, from, to, quality);
+    let skip = to as usize / 20;
+
+    db(rms(output[skip..output.len() - skip].iter().copied()) / rms(input.iter().copied()))
+}
+
+// The highest frequency each quality is flat up to, as a fraction of the lower rate,
+// and how far down the passband error and the aliasing have to be
+fn expectations(quality: ResampleQuality) -> (f64, f64) {
+    match quality {
+        ResampleQuality::Fast => (0.3, -60.0),
+        ResampleQuality::Normal => (0.4, -85.0),
+        ResampleQuality::Best => (0.45, -100.0),
+    }
+}
+
+#[test]
+fn upsampling_keeps_the_passband() {
+    for quality in QUALITIES {
+        let (edge, limit) = expectations(quality);
+        let error = passband_error(44100, 48000, 44100.0 * edge, quality);
+        assert!(error < limit, "{:?}: {:.1}dB", quality, error);
+    }
+}
+
+#[test]
+fn downsampling_keeps_the_passband() {
+    for quality in QUALITIES {
+        let (edge, limit) = expectations(quality);
+        let error = passband_error(48000, 16000, 16000.0 * edge, quality);
+        assert!(error < limit, "{:?}: {:.1}dB", quality, error);
+    }
+}
+
+#[test]
+fn downsampling_rejects_aliasing() {
+    for quality in QUALITIES {
+        let (_, limit) = expectations(quality);
+        for (from, to) in [(48000, 16000), (44100, 16000), (48000, 8000)] {
+            let level = aliasing(from, to, quality);
+            assert!(
+                level < limit,
+                "{:?} {} -> {}: {:.1}dB",
+                quality,
+                from,
+                to,
+                level
+            );
+        }
+    }
+}
+
+#[test]
+fn length_follows_the_rate() {
+    let input = vec![0.25f32; 44100 * 2];
+
+    let output = resample(&input, 2, 44100, 48000, ResampleQuality::Normal);
+    assert_eq!(output.len(), 48000 * 2);
+
+    let output = resample(&input, 1, 44100, 16000, ResampleQuality::Normal);
+    assert_eq!(output.len(), 16000 * 2);
+
+    assert_eq!(
+        resample(&input, 1, 44100, 44100, ResampleQuality::Normal),
+        input
+    );
+}
+
+#[test]
+fn constant_signal_stays_constant() {
+    let input = vec![0.25f32; 44100];
+
+    for quality in QUALITIES {
+        let output = resample(&input, 1, 44100, 48000, quality);
+        for sample in &output[4800..output.len() - 4800] {
+            assert!((sample - 0.25).abs() < 1e-4, "{:?}: {}", quality, sample);
+        }
+    }
+}
+
+#[test]
+fn channels_stay_apart() {
+    let left = sweep(44100, 20.0, 10000.0, 1.0);
+    let input: Vec<f32> = left.iter().flat_map(|&l| [l, 0.0]).collect();
+
+    let output = resample(&input, 2, 44100, 48000, ResampleQuality::Normal);
+
+    let right = rms(output.iter().skip(1).step_by(2).copied());
+    assert_eq!(right, 0.0);
+
+    let expected: Vec<f32> = (0..output.len() / 2)
+        .map(|i| sweep_at(i as f64 / 48000.0, 20.0, 10000.0, 1.0))
+        .collect();
+    let error = rms(output
+        .iter()
+        .step_by(2)
+        .zip(&expected)
+        .skip(2400)
+        .take(expected.len() - 4800)
+        .map(|(a, b)| a - b));
+    assert!(db(error / 0.5) < -80.0, "{:.1}dB", db(error / 0.5));
+}
+
+#[test]
+fn streaming_matches_all_at_once() {
+    // stereo, with the channels different so mixing them up shows
+    let input: Vec<f32> = sweep(44100, 20.0, 15000.0, 0.5)
+        .into_iter()
+        .flat_map(|s| [s, -s * 0.5])
+        .collect();
+
+    for (from, to) in [
+        (44100, 48000),
+        (48000, 16000),
+        (44100, 44100),
+        (22050, 48000),
+    ] {
+        let quality = ResampleQuality::Normal;
+        let expected = resample(&input, 2, from, to, quality);
+
+        // uneven pieces, some a lot shorter than the filter
+        let mut resampler = Resampler::new(2, from, to, quality);
+        let mut streamed = Vec::new();
+        let mut rest = &input[..];
+        for frames in [37, 1, 4096, 480].into_iter().cycle() {
+            if rest.is_empty() {
+                break;
+            }
+            let (piece, after) = rest.split_at((frames * 2).min(rest.len()));
+            streamed.extend(resampler.process(piece));
+            rest = after;
+        }
+        streamed.extend(resampler.finish());
+
+        assert_eq!(streamed, expected, "{} -> {}", from, to);
+    }
+}
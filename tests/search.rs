@@ -0,0 +1,120 @@
+mod common;
+
+use oxygen::{Db, Error};
+
+fn found(db: &Db, query: &str) -> Vec<String> {
+    let mut names: Vec<_> = db
+        .search(query)
+        .unwrap()
+        .into_iter()
+        .map(|result| result.meta.clip_name)
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn finds_names_notes_and_tags() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["groceries", "standup", "walk"]);
+
+    db.set_notes("standup", Some("talked about the release plan"))
+        .unwrap();
+    db.add_tag("walk", "ideas").unwrap();
+
+    assert_eq!(found(&db, "groceries"), ["groceries"]);
+    assert_eq!(found(&db, "release"), ["standup"]);
+    assert_eq!(found(&db, "ideas"), ["walk"]);
+    assert_eq!(found(&db, "\"release plan\""), ["standup"]);
+    assert!(found(&db, "\"plan release\"").is_empty());
+    assert_eq!(found(&db, "gro*"), ["groceries"]);
+
+    let results = db.search("release").unwrap();
+    assert!(
+        results[0].snippet.contains("[release]"),
+        "{}",
+        results[0].snippet
+    );
+}
+
+#[test]
+fn the_index_follows_the_clips() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["walk"]);
+
+    db.add_tag("walk", "ideas").unwrap();
+    db.remove_tag("walk", "ideas").unwrap();
+    assert!(found(&db, "ideas").is_empty());
+
+    db.set_notes("walk", Some("rain")).unwrap();
+    db.set_notes("walk", Some("sun")).unwrap();
+    assert!(found(&db, "rain").is_empty());
+    assert_eq!(found(&db, "sun"), ["walk"]);
+
+    db.rename("walk", "stroll").unwrap();
+    assert!(found(&db, "walk").is_empty());
+    assert_eq!(found(&db, "stroll"), ["stroll"]);
+
+    // clips in the trash aren't found, and are gone from the index once deleted for good
+    db.delete("stroll").unwrap();
+    assert!(found(&db, "sun").is_empty());
+    db.empty_trash().unwrap();
+    assert!(found(&db, "sun").is_empty());
+}
+
+#[test]
+fn best_matches_come_first() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["one", "two"]);
+
+    db.set_notes("one", Some("plan")).unwrap();
+    db.set_notes("two", Some("plan plan plan, the plan"))
+        .unwrap();
+
+    let results = db.search("plan").unwrap();
+    let names: Vec<_> = results.iter().map(|r| r.meta.clip_name.as_str()).collect();
+    assert_eq!(names, ["two", "one"]);
+    assert!(results[0].rank <= results[1].rank);
+}
+
+#[test]
+fn punctuation_is_searched_for_not_parsed() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["2026-10-18_09-30-00", "walk-in", "other"]);
+
+    db.set_notes("other", Some("don't forget the \"big\" one: the plan"))
+        .unwrap();
+
+    // the name recordings get by default, and the date in it
+    assert_eq!(found(&db, "2026-10-18_09-30-00"), ["2026-10-18_09-30-00"]);
+    assert_eq!(found(&db, "2026-10-18"), ["2026-10-18_09-30-00"]);
+    assert_eq!(found(&db, "walk-in"), ["walk-in"]);
+    assert_eq!(found(&db, "don't"), ["other"]);
+    assert_eq!(found(&db, "one: the"), ["other"]);
+    assert_eq!(found(&db, "big\""), ["other"]);
+    // words FTS5 would take as operators or columns
+    assert_eq!(found(&db, "plan OR nothing"), Vec::<String>::new());
+    assert_eq!(found(&db, "NOT plan"), Vec::<String>::new());
+    assert_eq!(found(&db, "name:walk"), Vec::<String>::new());
+
+    // phrases and prefixes still work
+    assert_eq!(found(&db, "\"forget the\""), ["other"]);
+    assert_eq!(found(&db, "\"forget th\"*"), ["other"]);
+    assert_eq!(found(&db, "\"forget the"), ["other"]);
+    assert_eq!(found(&db, "walk-*"), ["walk-in"]);
+    assert_eq!(found(&db, "2026-10*"), ["2026-10-18_09-30-00"]);
+}
+
+#[test]
+fn an_empty_search_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["walk"]);
+
+    for query in ["", "  ", "*", "\"\""] {
+        assert!(
+            matches!(db.search(query), Err(Error::InvalidSearch(..))),
+            "{:?}",
+            query
+        );
+    }
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use chrono::{TimeZone, Utc};
+use oxygen::internal_encoding::{EncoderSettings, HeaderV2, decode_v2};
+use oxygen::{Marker, ResampleQuality, Spool, spool};
+
+// A second of a 440Hz tone, with the second channel at half the level
+fn tone(sample_rate: u32, channels: u16) -> Vec<f32> {
+    (0..sample_rate)
+        .flat_map(|i| {
+            let sample = (i as f32 * 440.0 * std::f32::consts::TAU / sample_rate as f32).sin();
+            (0..channels).map(move |channel| sample / (channel + 1) as f32)
+        })
+        .collect()
+}
+
+// Spools `samples` in chunks the size a device would hand over
+fn spooled(dir: &tempfile::TempDir, sample_rate: u32, channels: u16, samples: &[f32]) -> Spool {
+    let spool = Spool::new(
+        dir.path(),
+        &EncoderSettings::default(),
+        ResampleQuality::default(),
+    );
+    let mut writer = spool
+        .writer(
+            "clip",
+            Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap(),
+            channels,
+            sample_rate,
+        )
+        .unwrap();
+
+    for (i, chunk) in samples.chunks(441 * channels as usize).enumerate() {
+        writer.write(chunk).unwrap();
+        if i == 50 {
+            writer.marker(Marker { position_ms: 500 }).unwrap();
+        }
+    }
+
+    spool
+}
+
+#[test]
+fn recovers_what_was_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let spool = spooled(&dir, 44100, 2, &tone(44100, 2));
+
+    assert_eq!(spool::spooled(dir.path()).unwrap(), [spool.path()]);
+
+    let recovered = spool::recover(spool.path()).unwrap();
+    assert_eq!(recovered.name, "clip");
+    assert_eq!(
+        recovered.date,
+        Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap()
+    );
+    assert_eq!(recovered.settings, EncoderSettings::default());
+    assert_eq!(recovered.markers, [Marker { position_ms: 500 }]);
+    assert!(!recovered.cut_short);
+
+    // resampled to 48kHz on the way, only whole packets make it
+    let header = HeaderV2::read(&recovered.encoded).unwrap();
+    assert_eq!(header.channels, 2);
+    assert_eq!(header.sample_rate, 48000);
+    assert_eq!(header.sample_count % 960, 0);
+    assert!(
+        header.sample_count > 48000 - 2 * 960,
+        "{}",
+        header.sample_count
+    );
+
+    let samples = decode_v2(&recovered.encoded).unwrap();
+    assert_eq!(samples.len(), header.sample_count as usize * 2);
+    // the tone is there, not silence
+    let loudest = samples.iter().fold(0f32, |l, s| l.max(s.abs()));
+    assert!(loudest > 0.5, "{}", loudest);
+
+    spool.remove().unwrap();
+    assert_eq!(
+        spool::spooled(dir.path()).unwrap(),
+        Vec::<std::path::PathBuf>::new()
+    );
+    // already gone is fine
+    spool.remove().unwrap();
+}
+
+#[test]
+fn a_cut_short_file_keeps_what_checks_out() {
+    let dir = tempfile::tempdir().unwrap();
+    let spool = spooled(&dir, 48000, 1, &tone(48000, 1));
+    let whole = spool::recover(spool.path()).unwrap();
+
+    // a crash in the middle of the last packet
+    let bytes = std::fs::read(spool.path()).unwrap();
+    std::fs::write(spool.path(), &bytes[..bytes.len() - 5]).unwrap();
+
+    let recovered = spool::recover(spool.path()).unwrap();
+    assert!(recovered.cut_short);
+    assert_eq!(
+        HeaderV2::read(&recovered.encoded).unwrap().sample_count,
+        HeaderV2::read(&whole.encoded).unwrap().sample_count - 960
+    );
+    decode_v2(&recovered.encoded).unwrap();
+
+    // and one with a flipped bit stops at that packet
+    let mut bytes = bytes;
+    let middle = bytes.len() / 2;
+    bytes[middle] ^= 1;
+    std::fs::write(spool.path(), &bytes).unwrap();
+
+    let recovered = spool::recover(spool.path()).unwrap();
+    assert!(recovered.cut_short);
+    assert!(
+        HeaderV2::read(&recovered.encoded).unwrap().sample_count
+            < HeaderV2::read(&whole.encoded).unwrap().sample_count
+    );
+}
+
+#[test]
+fn a_recording_still_going_is_not_left_behind() {
+    let dir = tempfile::tempdir().unwrap();
+    let spool = Spool::new(
+        dir.path(),
+        &EncoderSettings::default(),
+        ResampleQuality::default(),
+    );
+
+    let writer = spool.writer("clip", Utc::now(), 1, 48000).unwrap();
+    assert_eq!(
+        spool::spooled(dir.path()).unwrap(),
+        Vec::<std::path::PathBuf>::new()
+    );
+
+    drop(writer);
+    assert_eq!(spool::spooled(dir.path()).unwrap(), [spool.path()]);
+}
+
+#[test]
+fn rejects_what_is_not_a_spool_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("other.spool");
+
+    for bytes in [&b""[..], b"OXSP", b"RIFF\x01\0\0\0\0", b"OXSP\x09\0\0\0\0"] {
+        std::fs::write(&path, bytes).unwrap();
+        assert!(spool::recover(&path).is_err(), "{:?}", bytes);
+    }
+
+    // the folder doesn't have to be there
+    assert!(
+        spool::spooled(dir.path().join("missing"))
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn recovered_clips_save_as_they_are() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::empty_db(&dir);
+    let spool = spooled(&dir, 48000, 1, &tone(48000, 1));
+    let recovered = spool::recover(spool.path()).unwrap();
+
+    assert!(!db.name_taken("clip").unwrap());
+    db.save_encoded(
+        "clip",
+        recovered.date,
+        &recovered.encoded,
+        &recovered.settings,
+    )
+    .unwrap();
+    assert!(db.name_taken("clip").unwrap());
+
+    let (meta, encoded) = db.encoded("clip").unwrap();
+    assert_eq!(encoded, recovered.encoded);
+    assert_eq!(meta.clip_date, recovered.date);
+    assert_eq!(meta.clip_channels, 1);
+    assert_eq!(meta.clip_sample_count, 48000 / 960 * 960);
+
+    let clip = db.load("clip").unwrap().unwrap();
+    assert_eq!(clip.frames(), 48000 / 960 * 960);
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use chrono::Utc;
+use oxygen::AudioClip;
+
+// A tone on the left channel, and nothing on the right one
+fn left_only(frames: usize) -> AudioClip {
+    AudioClip::new(
+        48000,
+        2,
+        (0..frames)
+            .flat_map(|i| {
+                let sample = (i as f32 * 440.0 * std::f32::consts::TAU / 48000.0).sin() / 2.0;
+                [sample, 0.0]
+            })
+            .collect(),
+        None,
+        "stereo".to_string(),
+        Utc::now(),
+    )
+}
+
+fn loudest(samples: impl Iterator<Item = f32>) -> f32 {
+    samples.fold(0f32, |l, s| l.max(s.abs()))
+}
+
+#[test]
+fn stereo_clips_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::empty_db(&dir);
+
+    db.save(&mut left_only(48000)).unwrap();
+
+    assert_eq!(db.info("stereo").unwrap().clip_channels, 2);
+
+    let clip = db.load("stereo").unwrap().unwrap();
+    assert_eq!(clip.channels, 2);
+    assert_eq!(clip.frames(), 48000);
+    assert_eq!(clip.samples.len(), 2 * 48000);
+
+    // still interleaved left, right, with each channel where it was
+    let left = loudest(clip.samples.iter().step_by(2).copied());
+    let right = loudest(clip.samples.iter().skip(1).step_by(2).copied());
+    assert!(left > 0.4, "{}", left);
+    // opus codes the channels together, so a little of the left leaks over
+    assert!(right < 0.1, "{}", right);
+}
+
+#[test]
+fn mono_clips_stay_mono() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::empty_db(&dir);
+
+    db.save(&mut common::silence("mono", 4800)).unwrap();
+
+    assert_eq!(db.info("mono").unwrap().clip_channels, 1);
+    let clip = db.load("mono").unwrap().unwrap();
+    assert_eq!(clip.channels, 1);
+    assert_eq!(clip.samples.len(), 4800);
+}
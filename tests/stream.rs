@@ -0,0 +1,234 @@
+use chrono::Utc;
+use oxygen::stream::{self, PlayState, RecordState, Taker};
+use oxygen::{AudioClip, Marker, RecordLimits};
+use rtrb::RingBuffer;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+fn empty(sample_rate: u32, channels: u16) -> AudioClip {
+    AudioClip::new(
+        sample_rate,
+        channels,
+        Vec::new(),
+        None,
+        "clip".to_string(),
+        Utc::now(),
+    )
+}
+
+// `frames` frames of every channel at `level`
+fn chunk(channels: u16, frames: usize, level: f32) -> Vec<f32> {
+    vec![level; frames * channels as usize]
+}
+
+#[test]
+fn keeps_nothing_past_the_limit() {
+    let limits = RecordLimits {
+        max_duration: Some(Duration::from_millis(100)),
+        ..Default::default()
+    };
+    let mut taker = Taker::new(empty(48000, 2), &limits, None);
+    assert_eq!(taker.max_frames(), 4800);
+
+    taker.keep(&chunk(2, 3000, 0.5));
+    taker.keep(&chunk(2, 3000, 0.5));
+    assert_eq!(taker.clip().frames(), 4800);
+    assert_eq!(taker.clip().samples.len(), 2 * 4800);
+    // the Vec didn't grow past it either
+    assert!(taker.clip().samples.capacity() <= 2 * 4800);
+
+    taker.keep(&chunk(2, 10, 0.5));
+    assert_eq!(taker.clip().frames(), 4800);
+    assert_eq!(taker.position_ms(), 100);
+}
+
+#[test]
+fn the_memory_limit_counts_the_resampled_copy() {
+    // room for a second of mono
+    let limits = RecordLimits {
+        max_bytes: 4 * 48000,
+        ..Default::default()
+    };
+
+    // stored as it is
+    assert_eq!(
+        Taker::new(empty(48000, 1), &limits, None).max_frames(),
+        48000
+    );
+    assert_eq!(
+        Taker::new(empty(16000, 1), &limits, None).max_frames(),
+        48000
+    );
+    assert_eq!(
+        Taker::new(empty(48000, 2), &limits, None).max_frames(),
+        24000
+    );
+
+    // resampled to 48kHz, and both are around for a while
+    let frames = Taker::new(empty(44100, 1), &limits, None).max_frames();
+    let resampled = frames * 48000 / 44100;
+    assert!(frames + resampled <= 48000, "{} + {}", frames, resampled);
+    assert!(
+        frames + resampled > 48000 - 10,
+        "{} + {}",
+        frames,
+        resampled
+    );
+}
+
+#[test]
+fn counts_the_silent_frames_at_the_end() {
+    let limits = RecordLimits {
+        silence_threshold_db: -20.0,
+        ..Default::default()
+    };
+    let mut taker = Taker::new(empty(48000, 2), &limits, None);
+
+    taker.keep(&chunk(2, 100, 0.5));
+    assert_eq!(taker.silent_frames(), 0);
+
+    // -20dBFS is 0.1
+    taker.keep(&chunk(2, 30, 0.05));
+    taker.keep(&chunk(2, 20, -0.05));
+    assert_eq!(taker.silent_frames(), 50);
+
+    // a frame is only silent if every channel is
+    taker.keep(&[0.0, 0.5]);
+    assert_eq!(taker.silent_frames(), 0);
+    taker.keep(&chunk(2, 7, 0.0));
+    assert_eq!(taker.silent_frames(), 7);
+}
+
+#[test]
+fn cutting_the_silence_moves_the_markers_with_it() {
+    let mut clip = AudioClip {
+        samples: [chunk(2, 48000, 0.5), chunk(2, 24000, 0.0)].concat(),
+        ..empty(48000, 2)
+    };
+    let mut markers = [
+        Marker { position_ms: 200 },
+        Marker { position_ms: 1000 },
+        Marker { position_ms: 1300 },
+    ];
+
+    stream::cut_silence(&mut clip, 24000, &mut markers);
+
+    assert_eq!(clip.frames(), 48000);
+    assert!(clip.samples.iter().all(|&s| s == 0.5));
+    // the ones made during the silence are at the new end
+    assert_eq!(
+        markers,
+        [
+            Marker { position_ms: 200 },
+            Marker { position_ms: 1000 },
+            Marker { position_ms: 1000 },
+        ]
+    );
+
+    // more silence than clip leaves nothing
+    stream::cut_silence(&mut clip, 100_000, &mut markers);
+    assert_eq!(clip.frames(), 0);
+    assert!(markers.iter().all(|marker| marker.position_ms == 0));
+}
+
+#[test]
+fn takes_whole_frames_from_the_ring() {
+    let mut taker = Taker::new(empty(48000, 2), &RecordLimits::default(), None);
+    let (mut tx, mut rx) = rtrb::RingBuffer::new(8);
+
+    // wraps around the end of the ring, so it comes out in two parts
+    for round in 0..3 {
+        for i in 0..6 {
+            tx.push(round as f32 + i as f32 / 10.0).unwrap();
+        }
+        taker.take(&mut rx);
+    }
+
+    assert_eq!(taker.clip().frames(), 9);
+    assert_eq!(&taker.clip().samples[12..], [2.0, 2.1, 2.2, 2.3, 2.4, 2.5]);
+    assert!(rx.is_empty());
+}
+
+#[test]
+fn a_full_ring_counts_an_overrun_and_keeps_whole_frames() {
+    let state = RecordState::default();
+    // room for 2.5 stereo frames
+    let (mut tx, mut rx) = RingBuffer::new(5);
+
+    // three channels from the device, the first two are kept
+    let input: Vec<i16> = (0..4).flat_map(|frame| [frame, 100 + frame, 200]).collect();
+    stream::send_input_data(&input, 3, 2, &state, &mut tx);
+
+    assert_eq!(state.overruns.load(Ordering::Relaxed), 1);
+    let chunk = rx.read_chunk(rx.slots()).unwrap();
+    let kept: Vec<f32> = chunk.into_iter().collect();
+    assert_eq!(
+        kept,
+        [0.0, 100.0, 1.0, 101.0].map(|s: f32| s / 32768.0),
+        "only the two frames that fit"
+    );
+
+    // with room again nothing is counted
+    stream::send_input_data(&input[..6], 3, 2, &state, &mut tx);
+    assert_eq!(state.overruns.load(Ordering::Relaxed), 1);
+    assert_eq!(rx.slots(), 4);
+}
+
+#[test]
+fn nothing_is_sent_while_paused() {
+    let state = RecordState::default();
+    let (mut tx, rx) = RingBuffer::new(8);
+
+    state.paused.store(true, Ordering::Relaxed);
+    stream::send_input_data(&[0.5f32; 16], 1, 1, &state, &mut tx);
+
+    assert!(rx.is_empty());
+    assert_eq!(state.overruns.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn an_empty_ring_counts_an_underrun() {
+    let state = PlayState::default();
+    let samples = [0.25f32; 10];
+    let mut fed = 0;
+    let (mut tx, mut rx) = RingBuffer::new(4);
+
+    stream::feed(&samples, &mut fed, &mut tx, &state);
+    assert_eq!(fed, 4);
+    assert!(!state.fed_all.load(Ordering::Acquire));
+
+    // asks for more than the ring has, the rest is silence
+    let mut output = [1.0f32; 6];
+    stream::write_output_data(&mut output, &mut rx, &state);
+    assert_eq!(output, [0.25, 0.25, 0.25, 0.25, 0.0, 0.0]);
+    assert_eq!(state.underruns.load(Ordering::Relaxed), 1);
+    assert!(!state.done.load(Ordering::Acquire));
+
+    // and a device format that isn't f32 gets silence as well
+    let mut output = [1i16; 2];
+    stream::write_output_data(&mut output, &mut rx, &state);
+    assert_eq!(output, [0, 0]);
+    assert_eq!(state.underruns.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn running_out_after_the_last_sample_ends_playback() {
+    let state = PlayState::default();
+    let samples: Vec<f32> = (0..6).map(|i| i as f32 / 10.0).collect();
+    let mut fed = 0;
+    let (mut tx, mut rx) = RingBuffer::new(4);
+
+    let mut played = Vec::new();
+    while !state.done.load(Ordering::Acquire) {
+        stream::feed(&samples, &mut fed, &mut tx, &state);
+        let mut output = [1.0f32; 3];
+        stream::write_output_data(&mut output, &mut rx, &state);
+        played.extend_from_slice(&output);
+    }
+
+    assert_eq!(fed, samples.len());
+    assert!(state.fed_all.load(Ordering::Acquire));
+    assert_eq!(&played[..6], &samples[..]);
+    assert!(played[6..].iter().all(|&s| s == 0.0));
+    assert_eq!(state.underruns.load(Ordering::Relaxed), 0);
+}
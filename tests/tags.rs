@@ -0,0 +1,72 @@
+mod common;
+
+use oxygen::{Db, Error};
+
+fn names(db: &Db, tags: &[&str]) -> Vec<String> {
+    let tags: Vec<String> = tags.iter().map(|tag| tag.to_string()).collect();
+
+    db.list(&tags)
+        .unwrap()
+        .into_iter()
+        .map(|meta| meta.clip_name)
+        .collect()
+}
+
+#[test]
+fn list_only_keeps_clips_with_every_tag() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["a", "b", "c"]);
+
+    db.add_tag("a", "work").unwrap();
+    db.add_tag("a", "idea").unwrap();
+    db.add_tag("b", "work").unwrap();
+
+    assert_eq!(names(&db, &[]), ["a", "b", "c"]);
+    assert_eq!(names(&db, &["work"]), ["a", "b"]);
+    assert_eq!(names(&db, &["work", "idea"]), ["a"]);
+    // asking for the same tag twice is asking for it once
+    assert_eq!(names(&db, &["work", " work "]), ["a", "b"]);
+    assert!(names(&db, &["nope"]).is_empty());
+
+    assert_eq!(db.info("a").unwrap().clip_tags, ["idea", "work"]);
+}
+
+#[test]
+fn tagging_twice_is_tagging_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["a"]);
+
+    db.add_tag("a", "work").unwrap();
+    db.add_tag("a", " work").unwrap();
+    assert_eq!(db.info("a").unwrap().clip_tags, ["work"]);
+
+    db.remove_tag("a", "work").unwrap();
+    assert!(db.info("a").unwrap().clip_tags.is_empty());
+    assert!(names(&db, &["work"]).is_empty());
+
+    // removing a tag the clip doesn't have is fine
+    db.remove_tag("a", "work").unwrap();
+}
+
+#[test]
+fn rejects_bad_tags_and_missing_clips() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["a"]);
+
+    for tag in ["", "  ", "a,b"] {
+        assert!(
+            matches!(db.add_tag("a", tag), Err(Error::InvalidTag(_))),
+            "{:?}",
+            tag
+        );
+    }
+    assert!(matches!(
+        db.list(&["a,b".to_string()]),
+        Err(Error::InvalidTag(_))
+    ));
+
+    assert!(matches!(
+        db.add_tag("missing", "work"),
+        Err(Error::ClipNotFound(_))
+    ));
+}
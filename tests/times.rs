@@ -0,0 +1,74 @@
+mod common;
+
+use oxygen::AudioClip;
+use std::process::Output;
+
+fn oxygen(db: &std::path::Path, args: &[&str]) -> Output {
+    common::oxygen(db).args(args).output().unwrap()
+}
+
+#[test]
+fn rejects_what_isnt_a_time() {
+    let dir = tempfile::tempdir().unwrap();
+    common::db_with_clips(&dir, &["clip"]);
+    let db = common::db_path(&dir);
+    let out = dir.path().join("out.wav");
+    let out = out.to_str().unwrap();
+
+    for time in [
+        "",
+        "-1",
+        "nan",
+        "inf",
+        "ten",
+        "1:2:3:4",
+        "a:10",
+        "1:-5", // not times at all
+        "1e300",
+        "1e20",
+        "99999999999999999999:00", // more than a `Duration` holds
+    ] {
+        for (command, flag) in [
+            (&["export", "clip", out][..], "--from"),
+            (&["export", "clip", out], "--to"),
+            (&["record", "new"], "--max-duration"),
+            (&["record", "new"], "--stop-on-silence"),
+        ] {
+            // `--flag=value` so that clap doesn't take `-1` for a flag
+            let value = format!("{}={}", flag, time);
+            let args = [command, &[value.as_str()]].concat();
+
+            let output = oxygen(&db, &args);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+
+            // a usage error from clap, not a panic
+            assert_eq!(output.status.code(), Some(2), "{:?}: {}", args, stderr);
+            assert!(stderr.contains("isn't a time"), "{:?}: {}", args, stderr);
+            assert!(!stderr.contains("panicked"), "{:?}: {}", args, stderr);
+        }
+    }
+
+    assert!(!std::path::Path::new(out).exists());
+}
+
+#[test]
+fn accepts_seconds_minutes_and_hours() {
+    let dir = tempfile::tempdir().unwrap();
+    common::db_with_clips(&dir, &["clip"]);
+    let db = common::db_path(&dir);
+    let out = dir.path().join("out.wav");
+    let out = out.to_str().unwrap();
+
+    for time in ["0.5", " 0.5 ", "0:00.5", "0:0:0.5"] {
+        let output = oxygen(&db, &["export", "clip", out, "--to", time]);
+        assert!(
+            output.status.success(),
+            "{:?}: {}",
+            time,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let clip = AudioClip::import("out".to_string(), out.to_string()).unwrap();
+        assert_eq!(clip.frames(), 24000, "{:?}", time);
+    }
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use oxygen::{Db, Segment, Transcript};
+use rusqlite::Connection;
+
+fn transcript(texts: &[&str]) -> Transcript {
+    Transcript {
+        segments: texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| Segment {
+                start_ms: i as u64 * 1000,
+                end_ms: (i as u64 + 1) * 1000,
+                text: text.to_string(),
+            })
+            .collect(),
+    }
+}
+
+fn texts(db: &Db, name: &str) -> Option<Vec<String>> {
+    db.transcript(name).unwrap().map(|transcript| {
+        transcript
+            .segments
+            .into_iter()
+            .map(|segment| segment.text)
+            .collect()
+    })
+}
+
+// straight from the file, the clip is gone so `Db::transcript` can't be asked
+fn transcript_rows(dir: &tempfile::TempDir) -> i64 {
+    Connection::open(common::db_path(dir))
+        .unwrap()
+        .query_row("SELECT COUNT(*) FROM transcripts", [], |row| row.get(0))
+        .unwrap()
+}
+
+#[test]
+fn a_clip_without_a_transcript_has_none() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["walk"]);
+
+    assert_eq!(texts(&db, "walk"), None);
+}
+
+#[test]
+fn saved_transcripts_come_back_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["walk"]);
+
+    db.save_transcript("walk", &transcript(&["one", "two", "three"]))
+        .unwrap();
+
+    let saved = db.transcript("walk").unwrap().unwrap();
+    assert_eq!(saved.text(), "one two three");
+    assert_eq!(saved.segments[1].start_ms, 1000);
+    assert_eq!(saved.segments[1].end_ms, 2000);
+}
+
+#[test]
+fn saving_again_replaces_the_transcript() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["walk"]);
+
+    db.save_transcript("walk", &transcript(&["one", "two", "three"]))
+        .unwrap();
+    db.save_transcript("walk", &transcript(&["four"])).unwrap();
+
+    assert_eq!(texts(&db, "walk"), Some(vec!["four".to_string()]));
+    assert_eq!(transcript_rows(&dir), 1);
+}
+
+#[test]
+fn transcripts_follow_their_clip_through_the_trash() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["walk", "run"]);
+    db.save_transcript("walk", &transcript(&["walking"]))
+        .unwrap();
+    db.save_transcript("run", &transcript(&["running"]))
+        .unwrap();
+
+    db.delete("walk").unwrap();
+    db.restore("walk").unwrap();
+    assert_eq!(texts(&db, "walk"), Some(vec!["walking".to_string()]));
+
+    db.delete("walk").unwrap();
+    db.empty_trash().unwrap();
+    assert_eq!(transcript_rows(&dir), 1);
+
+    db.delete("run").unwrap();
+    db.purge_trash(chrono::Duration::minutes(-1)).unwrap();
+    assert_eq!(transcript_rows(&dir), 0);
+}
+
+#[cfg(not(feature = "transcribe"))]
+#[test]
+fn record_transcribe_gives_up_before_recording_without_the_feature() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = common::db_path(&dir);
+
+    let output = common::oxygen(&db_path)
+        .args(["record", "--transcribe", "--model", "model.bin", "walk"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("built without the `transcribe` feature")
+    );
+    assert!(Db::open_at(&db_path).unwrap().list(&[]).unwrap().is_empty());
+}
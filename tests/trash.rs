@@ -0,0 +1,131 @@
+mod common;
+
+use oxygen::{Db, Error};
+
+fn listed(db: &Db) -> Vec<String> {
+    db.list(&[])
+        .unwrap()
+        .into_iter()
+        .map(|meta| meta.clip_name)
+        .collect()
+}
+
+fn trashed(db: &Db) -> Vec<String> {
+    db.trash()
+        .unwrap()
+        .into_iter()
+        .map(|clip| clip.meta.clip_name)
+        .collect()
+}
+
+#[test]
+fn deleted_clips_can_be_restored() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["a", "b"]);
+
+    db.add_tag("a", "work").unwrap();
+    db.delete("a").unwrap();
+
+    assert_eq!(listed(&db), ["b"]);
+    assert_eq!(trashed(&db), ["a"]);
+    assert!(db.in_trash("a").unwrap());
+    assert!(db.load("a").unwrap().is_none());
+    assert!(matches!(db.info("a"), Err(Error::ClipNotFound(_))));
+    // it's not in the trash twice
+    assert!(matches!(db.delete("a"), Err(Error::ClipNotFound(_))));
+
+    db.restore("a").unwrap();
+    assert_eq!(listed(&db), ["a", "b"]);
+    assert!(trashed(&db).is_empty());
+    // and it comes back with everything it had
+    assert_eq!(db.info("a").unwrap().clip_tags, ["work"]);
+    assert!(db.load("a").unwrap().is_some());
+
+    assert!(matches!(db.restore("a"), Err(Error::NotInTrash(_))));
+    assert!(matches!(db.restore("missing"), Err(Error::NotInTrash(_))));
+}
+
+#[test]
+fn emptying_the_trash_deletes_for_good() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["a", "b", "c"]);
+
+    db.delete("a").unwrap();
+    db.delete("b").unwrap();
+    assert_eq!(db.empty_trash().unwrap(), 2);
+
+    assert!(trashed(&db).is_empty());
+    assert_eq!(listed(&db), ["c"]);
+    assert!(!db.name_taken("a").unwrap());
+    assert_eq!(db.empty_trash().unwrap(), 0);
+}
+
+#[test]
+fn purging_keeps_what_was_deleted_recently() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["a"]);
+
+    db.delete("a").unwrap();
+    assert_eq!(db.purge_trash(chrono::Duration::days(30)).unwrap(), 0);
+    assert_eq!(trashed(&db), ["a"]);
+
+    // anything deleted before a minute from now has been there long enough
+    assert_eq!(db.purge_trash(chrono::Duration::minutes(-1)).unwrap(), 1);
+    assert!(trashed(&db).is_empty());
+}
+
+#[test]
+fn purging_with_a_retention_longer_than_dates_go_keeps_everything() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["a"]);
+
+    db.delete("a").unwrap();
+    assert_eq!(
+        db.purge_trash(chrono::Duration::days(200_000_000)).unwrap(),
+        0
+    );
+    assert_eq!(trashed(&db), ["a"]);
+
+    // and the same from the command line, which purges on every run
+    let output = common::oxygen(&common::db_path(&dir))
+        .env("OXYGEN_TRASH_DAYS", u32::MAX.to_string())
+        .arg("list")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(trashed(&db), ["a"]);
+}
+
+#[test]
+fn a_clip_in_the_trash_keeps_its_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["a", "b"]);
+
+    db.delete("a").unwrap();
+    assert!(db.name_taken("a").unwrap());
+    assert!(matches!(db.rename("b", "a"), Err(Error::NameTaken(_))));
+}
+
+#[test]
+fn recording_over_a_clip_in_the_trash_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::db_with_clips(&dir, &["a", "b"]);
+    db.delete("a").unwrap();
+
+    // refused before a device is looked for, so this runs without one
+    let record = |name: &str| {
+        let output = common::oxygen(&common::db_path(&dir))
+            .args(["record", name])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    };
+
+    assert!(record("a").contains("is in the trash"));
+    assert!(record("b").contains("already exists"));
+}
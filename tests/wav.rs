@@ -0,0 +1,204 @@
+use chrono::Utc;
+use oxygen::{AudioClip, Error, Metadata, WavSettings};
+use std::f64::consts::PI;
+
+fn sine(rate: u32, len: usize, frequency: f64, amplitude: f64) -> Vec<f32> {
+    (0..len)
+        .map(|i| (amplitude * (2.0 * PI * frequency * i as f64 / rate as f64).sin()) as f32)
+        .collect()
+}
+
+fn clip(channels: u16, samples: Vec<f32>) -> AudioClip {
+    AudioClip::new(
+        44100,
+        channels,
+        samples,
+        None,
+        "wav".to_string(),
+        Utc::now(),
+    )
+}
+
+// What the samples look like once stored as `bits` bit integers without dither
+fn quantize(samples: &[f32], bits: u8) -> Vec<f32> {
+    let scale = (1i64 << (bits - 1)) as f64;
+    samples
+        .iter()
+        .map(|&s| ((s as f64 * scale).round().clamp(-scale, scale - 1.0) / scale) as f32)
+        .collect()
+}
+
+// Exports the clip as wav, returning its spec and the clip imported again
+fn round_trip(clip: &AudioClip, settings: &WavSettings) -> (hound::WavSpec, AudioClip) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("clip.wav").to_str().unwrap().to_string();
+
+    clip.export_wav(&path, settings, &Metadata::default())
+        .unwrap();
+    let spec = hound::WavReader::open(&path).unwrap().spec();
+
+    (spec, AudioClip::import("wav".to_string(), path).unwrap())
+}
+
+#[test]
+fn default_is_32_bit_float() {
+    let clip = clip(2, sine(44100, 2000, 440.0, 0.5));
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("clip.wav").to_str().unwrap().to_string();
+
+    clip.export(&path).unwrap();
+
+    let spec = hound::WavReader::open(&path).unwrap().spec();
+    assert_eq!(spec.bits_per_sample, 32);
+    assert_eq!(spec.sample_format, hound::SampleFormat::Float);
+    assert_eq!(spec.channels, 2);
+
+    let imported = AudioClip::import("wav".to_string(), path).unwrap();
+    assert_eq!(imported.samples, clip.samples);
+}
+
+#[test]
+fn integer_formats_round_trip() {
+    // a bit past full scale so clipping gets checked as well
+    let clip = clip(2, sine(44100, 5000, 440.0, 1.2));
+
+    for bits_per_sample in [16, 24, 32] {
+        let (spec, imported) = round_trip(
+            &clip,
+            &WavSettings {
+                bits_per_sample,
+                float: false,
+                dither: false,
+            },
+        );
+
+        assert_eq!(spec.bits_per_sample, bits_per_sample as u16);
+        assert_eq!(spec.sample_format, hound::SampleFormat::Int);
+        assert_eq!(imported.channels, 2);
+        assert!(
+            imported.samples == quantize(&clip.samples, bits_per_sample),
+            "{}: samples differ",
+            bits_per_sample
+        );
+    }
+}
+
+#[test]
+fn dither_stays_within_a_step() {
+    let clip = clip(1, sine(44100, 10000, 440.0, 0.5));
+
+    for bits_per_sample in [16, 24] {
+        let (_, imported) = round_trip(
+            &clip,
+            &WavSettings {
+                bits_per_sample,
+                float: false,
+                dither: true,
+            },
+        );
+        let step = 1.0 / (1i64 << (bits_per_sample - 1)) as f32;
+
+        let rounded = quantize(&clip.samples, bits_per_sample);
+        assert_ne!(imported.samples, rounded, "{}", bits_per_sample);
+        for (a, b) in imported.samples.iter().zip(&rounded) {
+            assert!((a - b).abs() <= step, "{} {} {}", bits_per_sample, a, b);
+        }
+    }
+}
+
+#[test]
+fn dither_keeps_what_rounding_loses() {
+    // a tone quieter than half a step rounds away to nothing
+    let step = 1.0 / 32768.0;
+    let quiet = sine(44100, 44100, 1000.0, 0.4 * step);
+    let clip = clip(1, quiet.clone());
+
+    let settings = |dither| WavSettings {
+        bits_per_sample: 16,
+        float: false,
+        dither,
+    };
+
+    let (_, rounded) = round_trip(&clip, &settings(false));
+    assert!(rounded.samples.iter().all(|&s| s == 0.0));
+
+    // but with dither it is still there under the noise
+    let (_, dithered) = round_trip(&clip, &settings(true));
+    let correlation: f64 = dithered
+        .samples
+        .iter()
+        .zip(&quiet)
+        .map(|(&a, &b)| a as f64 * b as f64)
+        .sum::<f64>()
+        / quiet.iter().map(|&s| (s as f64).powi(2)).sum::<f64>();
+    assert!((correlation - 1.0).abs() < 0.1, "{}", correlation);
+}
+
+#[test]
+fn settings_are_checked() {
+    for (bits_per_sample, float) in [(16, true), (24, true), (8, false), (20, false)] {
+        let settings = WavSettings {
+            bits_per_sample,
+            float,
+            dither: true,
+        };
+        assert!(matches!(
+            settings.validate(),
+            Err(Error::InvalidExportSettings(_))
+        ));
+    }
+}
+
+// The id and size of every chunk in a wav file, in the order they are in
+fn chunks(bytes: &[u8]) -> Vec<(String, usize)> {
+    let size = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+    let mut chunks = Vec::new();
+
+    // past `RIFF`, the size and `WAVE`
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let chunk_size = size(offset + 4);
+        chunks.push((
+            String::from_utf8_lossy(&bytes[offset..offset + 4]).into_owned(),
+            chunk_size,
+        ));
+        offset += 8 + chunk_size + chunk_size % 2;
+    }
+
+    chunks
+}
+
+#[test]
+fn metadata_comes_before_the_samples() {
+    let clip = clip(2, sine(44100, 2000, 440.0, 0.5));
+    let metadata = Metadata {
+        notes: Some("an odd number of bytes".to_string()),
+        ..Default::default()
+    };
+
+    for (bits_per_sample, float) in [(32, true), (24, false), (16, false)] {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clip.wav").to_str().unwrap().to_string();
+        let settings = WavSettings {
+            bits_per_sample,
+            float,
+            dither: false,
+        };
+        clip.export_wav(&path, &settings, &metadata).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(
+            u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize,
+            bytes.len() - 8
+        );
+
+        let chunks = chunks(&bytes);
+        let ids: Vec<_> = chunks.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, ["fmt ", "LIST", "data"], "{}", bits_per_sample);
+        assert_eq!(
+            chunks[2].1,
+            clip.samples.len() * bits_per_sample as usize / 8
+        );
+    }
+}